@@ -0,0 +1,126 @@
+//!
+//! Runs a scenario file without the web interface, as fast as possible, and
+//! writes the per round metrics. The results are the same as running the
+//! scenario in the browser.
+//!
+//! Usage: headless <scenario.toml|json> [--rounds N] [--format csv|jsonl] [--out FILE] [--events FILE]
+//!                 [--save-scenario FILE]
+//!
+
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+use gtr::engine::Engine;
+use gtr::export::{self, Format};
+use gtr::metrics::Metrics;
+use gtr::scenario::Scenario;
+
+const USAGE: &str =
+    "usage: headless <scenario.toml|json> [--rounds N] [--format csv|jsonl] [--out FILE] [--events FILE] \
+     [--save-scenario FILE]";
+
+struct Args {
+    scenario: PathBuf,
+    /// Overrides the duration of the scenario
+    rounds: Option<u64>,
+    format: Format,
+    /// Metrics go to stdout when not set
+    out: Option<PathBuf>,
+    events: Option<PathBuf>,
+    /// Where to write the scenario as it was run, with its satellites listed
+    save_scenario: Option<PathBuf>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut scenario = None;
+    let mut rounds = None;
+    let mut format = Format::Csv;
+    let mut out = None;
+    let mut events = None;
+    let mut save_scenario = None;
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{arg} needs a value"));
+        match arg.as_str() {
+            "--rounds" => rounds = Some(value()?.parse().map_err(|e| format!("invalid --rounds: {e}"))?),
+            "--format" => {
+                format = match value()?.as_str() {
+                    "csv" => Format::Csv,
+                    "jsonl" => Format::JsonLines,
+                    other => return Err(format!("unknown format {other}, use csv or jsonl")),
+                }
+            }
+            "--out" => out = Some(PathBuf::from(value()?)),
+            "--events" => events = Some(PathBuf::from(value()?)),
+            "--save-scenario" => save_scenario = Some(PathBuf::from(value()?)),
+            _ if scenario.is_none() && !arg.starts_with("--") => scenario = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument {arg}")),
+        }
+    }
+
+    Ok(Args {
+        scenario: scenario.ok_or("missing scenario file")?,
+        rounds,
+        format,
+        out,
+        events,
+        save_scenario,
+    })
+}
+
+fn write(path: &Path, contents: &str) -> Result<(), String> {
+    std::fs::write(path, contents).map_err(|e| format!("couldn't write {}: {e}", path.display()))
+}
+
+/// Write to stdout, stopping quietly when the reader went away, e.g. when piped into `head`
+fn write_stdout(contents: &str) -> Result<(), String> {
+    let mut stdout = io::stdout().lock();
+    match write!(stdout, "{contents}").and_then(|()| stdout.flush()) {
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result.map_err(|e| format!("couldn't write the metrics: {e}")),
+    }
+}
+
+fn run(args: Args) -> Result<(), String> {
+    let mut scenario = Scenario::load(&args.scenario).map_err(|e| e.to_string())?;
+    if args.rounds.is_some() {
+        scenario.duration = args.rounds;
+    }
+    if scenario.duration.is_none() {
+        return Err("the scenario has no duration, pass --rounds".to_string());
+    }
+    if let Some(path) = &args.save_scenario {
+        let run = Scenario { satellites: scenario.satellite_definitions(), ..scenario.clone() };
+        run.save(path).map_err(|e| format!("couldn't save the scenario to {}: {e}", path.display()))?;
+    }
+
+    let mut metrics = Metrics::new();
+    let mut events = Vec::new();
+    let mut engine = Engine::new(scenario);
+    engine.run_to_end(|mut report| {
+        events.append(&mut report.events);
+        metrics.record_report(report);
+    });
+
+    let output = export::metrics(metrics.samples(), args.format);
+    match &args.out {
+        Some(path) => write(path, &output)?,
+        None => write_stdout(&output)?,
+    }
+    if let Some(path) = &args.events {
+        write(path, &export::events(&events, args.format))?;
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let result = parse_args(std::env::args().skip(1)).and_then(run);
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("{e}\n{USAGE}");
+            ExitCode::FAILURE
+        }
+    }
+}
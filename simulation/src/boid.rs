@@ -105,7 +105,7 @@ impl Boid {
         ret
     }
 
-    pub fn update_all(settings: &Settings, boids: &mut Vec<Boid>) -> (Vec<Self>, QuadTree<usize>) {
+    pub fn update_all(settings: &Settings, boids: &mut Vec<Boid>) -> (Vec<Self>, QuadTree<f32, usize>) {
         let mut ret = Vec::with_capacity(boids.len());
 
         // Create quadtree
@@ -0,0 +1,74 @@
+//! This module keeps the simulated time. Every tick advances the clock by the
+//! tick interval scaled by the time warp, so orbits that take a day can be
+//! watched in minutes, and the simulated UTC time is the epoch of the scenario
+//! plus the simulated time elapsed.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Largest supported time warp
+pub const MAX_TIME_WARP: f32 = 5000.0;
+/// Time warp used when nothing else was picked
+pub const DEFAULT_TIME_WARP: f32 = 1000.0;
+
+/// Simulated seconds that pass in `wall_ms` milliseconds of real time sped up by `warp`
+pub fn warped_secs(wall_ms: u64, warp: f32) -> f64 {
+    wall_ms as f64 * warp.clamp(0.0, MAX_TIME_WARP) as f64 / 1000.0
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct SimClock {
+    epoch: DateTime<Utc>,
+    /// Simulated time since the epoch, in milliseconds
+    elapsed_ms: f64,
+}
+
+impl SimClock {
+    pub fn new(epoch: DateTime<Utc>) -> Self {
+        Self { epoch, elapsed_ms: 0.0 }
+    }
+
+    /// Move the clock forward to `secs` simulated seconds since the epoch, it never goes back
+    pub fn advance_to(&mut self, secs: f64) {
+        self.elapsed_ms = self.elapsed_ms.max(secs * 1000.0);
+    }
+
+    pub fn epoch(&self) -> DateTime<Utc> {
+        self.epoch
+    }
+
+    /// Simulated seconds since the epoch
+    pub fn elapsed_secs(&self) -> f64 {
+        self.elapsed_ms / 1000.0
+    }
+
+    /// Simulated UTC time
+    pub fn now(&self) -> DateTime<Utc> {
+        self.epoch + Duration::milliseconds(self.elapsed_ms as i64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn warps_time_from_the_epoch() {
+        let epoch = Utc.with_ymd_and_hms(2024, 3, 20, 3, 6, 0).unwrap();
+        let mut clock = SimClock::new(epoch);
+
+        // One second of real time at a thousand times
+        for _ in 0..20 {
+            clock.advance_to(clock.elapsed_secs() + warped_secs(50, DEFAULT_TIME_WARP));
+        }
+        assert_eq!(clock.elapsed_secs(), 1000.0);
+        assert_eq!(clock.now(), epoch + Duration::seconds(1000));
+
+        // The clock doesn't go back and warps beyond the maximum are clamped
+        clock.advance_to(10.0);
+        assert_eq!(clock.elapsed_secs(), 1000.0);
+        assert_eq!(warped_secs(1000, 2.0 * MAX_TIME_WARP), MAX_TIME_WARP as f64);
+        assert_eq!(clock.epoch(), epoch);
+    }
+}
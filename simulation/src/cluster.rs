@@ -1,23 +1,26 @@
-/// This module will encapsulate information for clusters in the
-/// network graph. A cluster is a group of nodes that are connected
-/// to each other and are not connected to any other nodes outside
-/// the cluster.
-///
-/// The cluster module will contain the following:
-/// 1. A cluster head, which is the node that will act as a gateway to Earth for the cluster.
-/// 2. cluster members, which are the nodes that are part of the cluster.
-/// 3. A cluster ID, which is a unique identifier for the cluster.
-/// 4. A cluster size, which is the number of nodes in the cluster.
-///
-
+//! This module will encapsulate information for clusters in the
+//! network graph. A cluster is a group of nodes that are connected
+//! to each other and are not connected to any other nodes outside
+//! the cluster.
+//!
+//! The cluster module will contain the following:
+//! 1. A cluster head, which is the node that will act as a gateway to Earth for the cluster.
+//! 2. cluster members, which are the nodes that are part of the cluster.
+//! 3. A cluster ID, which is a unique identifier for the cluster.
+//! 4. A cluster size, which is the number of nodes in the cluster.
+//!
+
+use crate::quadtree::{box2d::Box2d, quadtree::QuadTree, types::Point};
 use crate::satellite::SatellitePosition;
 use crate::simulation::SIZE;
 
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+use serde::{Deserialize, Serialize};
 use yew::{html, Html};
 use rand::Rng;
 
 
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct Cluster {
     head: usize,
     members: Vec<usize>,
@@ -63,8 +66,10 @@ impl Cluster {
     }
 }
 
+/// Clusters by head, kept in order of their head so rounds visit them in the same order on every run
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct ClusterMap {
-    map: HashMap<usize, Cluster>,
+    map: BTreeMap<usize, Cluster>,
 }
 
 impl Default for ClusterMap {
@@ -76,7 +81,7 @@ impl Default for ClusterMap {
 impl ClusterMap {
     pub fn new() -> Self {
         Self {
-            map: HashMap::new(),
+            map: BTreeMap::new(),
         }
     }
 
@@ -103,6 +108,68 @@ impl ClusterMap {
     pub fn clusters_mut(&mut self) -> Vec<&mut Cluster> {
         self.map.values_mut().collect()
     }
+
+    /// Head of the cluster each of `count` satellites belongs to, indexed by satellite id
+    pub fn membership(&self, count: usize) -> Vec<Option<usize>> {
+        let mut heads = vec![None; count];
+        for cluster in self.map.values() {
+            for &member in cluster.members() {
+                heads[member] = Some(cluster.head());
+            }
+        }
+        heads
+    }
+}
+
+/// Search of the already elected heads around a candidate, as made by `elect_heads`
+#[derive(Clone, Debug)]
+pub struct HeadQuery {
+    pub center: Point<f32>,
+    pub radius: f32,
+    /// Nodes of the head quadtree the search descended into
+    pub visited: Vec<Box2d<f32>>,
+    /// Heads found within the radius
+    pub found: Vec<Point<f32>>,
+}
+
+/// Elect cluster heads from `candidates`, in order, skipping any candidate that is
+/// closer than `min_distance` to an already elected head. `heads` is cleared and
+/// filled with the elected heads, which are also returned in election order.
+/// `queries` is refilled with the search made for each candidate, indexed by satellite id.
+pub fn elect_heads(
+    candidates: impl IntoIterator<Item = usize>,
+    satellites: &[SatellitePosition],
+    min_distance: f32,
+    heads: &mut QuadTree<f32, usize>,
+    queries: &mut Vec<Option<HeadQuery>>,
+) -> Vec<usize> {
+    let mut elected = Vec::new();
+    heads.clear();
+    queries.clear();
+    queries.resize(satellites.len(), None);
+
+    for id in candidates {
+        let position = satellites[id].screen_position();
+        let point = Point::from(position);
+
+        let mut visited = Vec::new();
+        let found = heads.query_radius_traced(&point, min_distance, |node| visited.push(node.clone()));
+        let too_close = found.iter().any(|(_, distance)| *distance < min_distance);
+
+        queries[id] = Some(HeadQuery {
+            center: point.clone(),
+            radius: min_distance,
+            visited,
+            found: found.into_iter().map(|(entry, _)| entry.point).collect(),
+        });
+
+        if !too_close {
+            heads.insert(point, id);
+            elected.push(id);
+        }
+    }
+
+    elected
 }
 
 pub fn render(cluster: &Cluster, satellites: &[SatellitePosition]) -> Html {
@@ -0,0 +1,138 @@
+use std::fmt::Write;
+
+use yew::{html, Component, Context, Html, Properties};
+
+const WIDTH: f32 = 240.0;
+const HEIGHT: f32 = 90.0;
+const PADDING: f32 = 4.0;
+
+/// Format a value the way the charts label it
+fn format_value(value: f32, percentage: bool) -> String {
+    if percentage {
+        format!("{:.1}%", 100.0 * value)
+    } else {
+        format!("{value:.2}")
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Properties)]
+pub struct LineChartProps {
+    pub label: &'static str,
+    /// `(round, value)` pairs sorted by round
+    pub series: Vec<(u64, f32)>,
+    /// Lower bound of the y axis, defaults to the smallest value
+    #[prop_or_default]
+    pub min: Option<f32>,
+    /// Upper bound of the y axis, defaults to the largest value
+    #[prop_or_default]
+    pub max: Option<f32>,
+    #[prop_or_default]
+    pub percentage: bool,
+}
+
+pub struct LineChart;
+impl Component for LineChart {
+    type Message = ();
+    type Properties = LineChartProps;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let LineChartProps {
+            label,
+            ref series,
+            min,
+            max,
+            percentage,
+        } = *ctx.props();
+
+        let min = min.unwrap_or_else(|| series.iter().map(|p| p.1).fold(f32::INFINITY, f32::min));
+        let max = max.unwrap_or_else(|| series.iter().map(|p| p.1).fold(f32::NEG_INFINITY, f32::max));
+        let span = if max > min { max - min } else { 1.0 };
+
+        let first_round = series.first().map(|p| p.0).unwrap_or(0);
+        let last_round = series.last().map(|p| p.0).unwrap_or(0);
+        let rounds = last_round.saturating_sub(first_round).max(1) as f32;
+
+        let mut points = String::new();
+        for &(round, value) in series {
+            let x = PADDING + (round - first_round) as f32 / rounds * (WIDTH - 2.0 * PADDING);
+            let y = HEIGHT - PADDING - (value - min) / span * (HEIGHT - 2.0 * PADDING);
+
+            // Write to string will never fail.
+            let _ = write!(points, "{x:.2},{y:.2} ");
+        }
+
+        let latest = series.last().map(|p| format_value(p.1, percentage)).unwrap_or_default();
+
+        html! {
+            <div class="chart">
+                <span class="chart__label">{ label }</span>
+                <span class="chart__value">{ latest }</span>
+                <svg class="chart__plot" viewBox={format!("0 0 {WIDTH} {HEIGHT}")}>
+                    <rect x="0" y="0" width={WIDTH.to_string()} height={HEIGHT.to_string()} class="chart__background" />
+                    <polyline {points} fill="none" stroke="#1abc9c" stroke-width="1.5" />
+                    <text x={PADDING.to_string()} y="12" class="chart__axis">{ format_value(max, percentage) }</text>
+                    <text x={PADDING.to_string()} y={(HEIGHT - PADDING).to_string()} class="chart__axis">{ format_value(min, percentage) }</text>
+                </svg>
+            </div>
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Properties)]
+pub struct HistogramProps {
+    pub label: &'static str,
+    /// Count per bin, the bins evenly split `0..max`
+    pub bins: Vec<usize>,
+    pub max: f32,
+}
+
+pub struct Histogram;
+impl Component for Histogram {
+    type Message = ();
+    type Properties = HistogramProps;
+
+    fn create(_ctx: &Context<Self>) -> Self {
+        Self
+    }
+
+    fn view(&self, ctx: &Context<Self>) -> Html {
+        let HistogramProps {
+            label,
+            ref bins,
+            max,
+        } = *ctx.props();
+
+        let highest = bins.iter().copied().max().unwrap_or(0).max(1) as f32;
+        let bar_width = (WIDTH - 2.0 * PADDING) / bins.len().max(1) as f32;
+
+        let bars = bins.iter().enumerate().map(|(bin, &count)| {
+            let height = count as f32 / highest * (HEIGHT - 2.0 * PADDING - 12.0);
+            let x = format!("{:.2}", PADDING + bin as f32 * bar_width);
+            let y = format!("{:.2}", HEIGHT - PADDING - height);
+            let from = max * bin as f32 / bins.len() as f32;
+            let to = max * (bin + 1) as f32 / bins.len() as f32;
+
+            html! {
+                <rect x={x} y={y} width={format!("{:.2}", bar_width - 1.0)} height={format!("{height:.2}")} fill="#1abc9c">
+                    <title>{ format!("{from:.0} - {to:.0}: {count}") }</title>
+                </rect>
+            }
+        }).collect::<Html>();
+
+        html! {
+            <div class="chart">
+                <span class="chart__label">{ label }</span>
+                <span class="chart__value">{ format!("0 - {max:.0}") }</span>
+                <svg class="chart__plot" viewBox={format!("0 0 {WIDTH} {HEIGHT}")}>
+                    <rect x="0" y="0" width={WIDTH.to_string()} height={HEIGHT.to_string()} class="chart__background" />
+                    { bars }
+                    <text x={PADDING.to_string()} y="12" class="chart__axis">{ highest.to_string() }</text>
+                </svg>
+            </div>
+        }
+    }
+}
@@ -0,0 +1,79 @@
+//!
+//! This component renders the most recent events of the run matching a filter on
+//! satellite, cluster and event type
+//!
+
+use web_sys::{HtmlInputElement, HtmlSelectElement};
+use yew::prelude::*;
+use crate::events::{EventFilter, EventKind, EventLog};
+
+// Number of matching events listed, most recent first
+const ROWS: usize = 200;
+
+fn parse_id(value: &str) -> Option<usize> {
+    value.trim().parse().ok()
+}
+
+pub fn render(log: &EventLog, filter: EventFilter, on_filter: Callback<EventFilter>) -> Html {
+    let on_satellite = on_filter.reform(move |e: Event| {
+        let input: HtmlInputElement = e.target_unchecked_into();
+        EventFilter { satellite: parse_id(&input.value()), ..filter }
+    });
+    let on_cluster = on_filter.reform(move |e: Event| {
+        let input: HtmlInputElement = e.target_unchecked_into();
+        EventFilter { cluster: parse_id(&input.value()), ..filter }
+    });
+    let on_kind = on_filter.reform(move |e: Event| {
+        let select: HtmlSelectElement = e.target_unchecked_into();
+        let kind = select.value().parse::<usize>().ok().and_then(|i| EventKind::ALL.get(i).copied());
+        EventFilter { kind, ..filter }
+    });
+
+    let rows = log
+        .filtered(&filter)
+        .rev()
+        .take(ROWS)
+        .map(|r| {
+            html! {
+                <tr>
+                    <td>{ r.round }</td>
+                    <td>{ r.event.kind().label() }</td>
+                    <td>{ r.event.satellite() }</td>
+                    <td>{ r.event.cluster() }</td>
+                    <td>{ r.event.value().map(|v| format!("{v:.2}")).unwrap_or_default() }</td>
+                </tr>
+            }
+        })
+        .collect::<Html>();
+
+    let id_value = |id: Option<usize>| id.map(|id| id.to_string()).unwrap_or_default();
+
+    html! {
+        <div class="events">
+            <div class="events__filters">
+                <input type="number" min="0" placeholder="Satellite"
+                    value={id_value(filter.satellite)} onchange={on_satellite} />
+                <input type="number" min="0" placeholder="Cluster"
+                    value={id_value(filter.cluster)} onchange={on_cluster} />
+                <select onchange={on_kind}>
+                    <option value="" selected={filter.kind.is_none()}>{ "All events" }</option>
+                    { for EventKind::ALL.iter().enumerate().map(|(i, kind)| html! {
+                        <option value={i.to_string()} selected={filter.kind == Some(*kind)}>{ kind.label() }</option>
+                    }) }
+                </select>
+            </div>
+            <div class="events__list">
+                <table>
+                    <tr>
+                        <th>{ "Round" }</th>
+                        <th>{ "Event" }</th>
+                        <th>{ "Satellite" }</th>
+                        <th>{ "Cluster" }</th>
+                        <th>{ "Value" }</th>
+                    </tr>
+                    { rows }
+                </table>
+            </div>
+        </div>
+    }
+}
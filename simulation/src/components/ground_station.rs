@@ -0,0 +1,33 @@
+//!
+//! This component renders a ground station of the scenario as a marker on the edge of the planet
+//!
+
+use yew::prelude::*;
+use crate::math::{Vector2D, TAU};
+use crate::scenario::GroundStation;
+use crate::simulation::SIZE;
+
+// Radius of the planet drawn in the background of the simulation window
+const PLANET_RADIUS: f32 = 20.0;
+const MARKER_SIZE: f32 = 6.0;
+
+/// `rotation` is how far the planet turned since the epoch, in radians
+pub fn render(station: &GroundStation, rotation: f32) -> Html {
+    let angle = (station.longitude / 360.0 * TAU + rotation) % TAU;
+    let center = Vector2D::new(SIZE.x / 2.0, SIZE.y / 2.0);
+    let base = center + Vector2D::from_polar(angle, PLANET_RADIUS);
+    let tip = center + Vector2D::from_polar(angle, PLANET_RADIUS + MARKER_SIZE);
+    let side = Vector2D::from_polar(angle + TAU / 4.0, MARKER_SIZE / 2.0);
+
+    let points = [base + side, tip, base - side]
+        .iter()
+        .map(|p| format!("{:.3},{:.3}", p.x, p.y))
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    html! {
+        <polygon points={points} fill="gold">
+            <title>{ station.name.clone() }</title>
+        </polygon>
+    }
+}
@@ -1,24 +1,67 @@
-///
-/// This component renders the info and state of the clicked Satellite component instance
-///
+//!
+//! This component renders the info and state of the clicked Satellite component instance
+//!
 
 use yew::prelude::*;
 use crate::simulation::SIZE;
+use crate::ledger::EnergyLedger;
 use crate::satellite::*;
 
+const WIDTH: f32 = 220.0;
+const HEIGHT: f32 = 220.0;
 
-pub fn render(props: &SatelliteProperties, pos: &SatellitePosition, game: &SatelliteEnergy) -> Html {
+// Area of the energy history sparkline inside the panel
+const SPARK_X: f32 = 16.0;
+const SPARK_Y: f32 = 72.0;
+const SPARK_WIDTH: f32 = WIDTH - 32.0;
+const SPARK_HEIGHT: f32 = 36.0;
+
+// Number of ledger entries listed, most recent first
+const LEDGER_ROWS: usize = 32;
+
+/// `ledger` is the ledger of `game`, or its part recorded up to the tick being replayed
+pub fn render(props: &SatelliteProperties, pos: &SatellitePosition, game: &SatelliteEnergy, ledger: &EnergyLedger) -> Html {
     // Calculate X position offset for rendering the panel from the satellite based on distance from screen borders
-    let x_offset = if pos.screen_position().x > (SIZE.x - 180.0) { -180.0 } else { 20.0 };
-    let y_offset = if pos.screen_position().y > (SIZE.y - 100.0) { -100.0 } else { 20.0 };
+    let x_offset = if pos.screen_position().x > (SIZE.x - (WIDTH + 20.0)) { -(WIDTH + 20.0) } else { 20.0 };
+    let y_offset = if pos.screen_position().y > (SIZE.y - (HEIGHT + 20.0)) { -(HEIGHT + 20.0) } else { 20.0 };
     let x = format!("{:.3}", pos.screen_position().x + x_offset);
     let y = format!("{:.3}", pos.screen_position().y + y_offset);
 
+    // Scale the recorded balances into the sparkline area
+    let max_energy = game.max_energy().max(f32::EPSILON);
+    let step = SPARK_WIDTH / (ledger.len().max(2) - 1) as f32;
+    let points = ledger
+        .balances()
+        .enumerate()
+        .map(|(i, balance)| {
+            let px = SPARK_X + i as f32 * step;
+            let py = SPARK_Y + SPARK_HEIGHT * (1.0 - (balance / max_energy).clamp(0.0, 1.0));
+            format!("{:.1},{:.1}", px, py)
+        })
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let rows = ledger
+        .entries()
+        .rev()
+        .take(LEDGER_ROWS)
+        .map(|entry| {
+            html! {
+                <tr>
+                    <td>{ entry.round }</td>
+                    <td>{ entry.cause.label() }</td>
+                    <td>{ format!("{:+.2}", entry.amount) }</td>
+                    <td>{ format!("{:.2}", entry.balance) }</td>
+                </tr>
+            }
+        })
+        .collect::<Html>();
+
     // Render a table in svg format for the satellite info
     html! {
         <svg id="info-panel" x={x} y={y}>
             // Render a rectangle with rounded corners
-            <rect x="0" y="0" width="160" height="80" fill="dark-gray" opacity="0.75" rx="15" />
+            <rect x="0" y="0" width={WIDTH.to_string()} height={HEIGHT.to_string()} fill="dark-gray" opacity="0.75" rx="15" />
 
             // Display ID of satellite
             <text x="16" y="26" font-weight="bold" fill="white">
@@ -34,6 +77,22 @@ pub fn render(props: &SatelliteProperties, pos: &SatellitePosition, game: &Satel
             <text x="16" y="62" font-weight="bold" fill="white">
                 {format!("Pe: {:.2}%", 100.0*game.prob_entering())}
             </text>
+
+            // Energy history over the recorded ledger
+            <rect x={SPARK_X.to_string()} y={SPARK_Y.to_string()} width={SPARK_WIDTH.to_string()} height={SPARK_HEIGHT.to_string()}
+                fill="none" stroke="gray" stroke-width="0.5" />
+            <polyline points={points} fill="none" stroke="#1abc9c" stroke-width="1.5" />
+
+            // Most recent energy changes and their causes
+            <foreignObject x="8" y={(SPARK_Y + SPARK_HEIGHT + 8.0).to_string()}
+                width={(WIDTH - 16.0).to_string()} height={(HEIGHT - SPARK_Y - SPARK_HEIGHT - 20.0).to_string()}>
+                <div class="ledger">
+                    <table>
+                        <tr><th>{"Round"}</th><th>{"Cause"}</th><th>{"Δ"}</th><th>{"Energy"}</th></tr>
+                        { rows }
+                    </table>
+                </div>
+            </foreignObject>
         </svg>
     }
 }
@@ -1,2 +1,8 @@
+pub mod chart;
+pub mod event_log;
+pub mod ground_station;
 pub mod info_panel;
+pub mod qtree_stats;
+pub mod query_overlay;
+pub mod run_summary;
 pub mod slider;
@@ -0,0 +1,55 @@
+//!
+//! This component renders the shape of the quadtree shown by the "Toggle QTree" overlay
+//!
+
+use yew::prelude::*;
+use crate::quadtree::quadtree::QuadTreeStats;
+
+const BAR_WIDTH: f32 = 12.0;
+const BAR_MAX_HEIGHT: f32 = 60.0;
+
+pub fn render(stats: &QuadTreeStats) -> Html {
+    let max_leaves = stats.entries_per_leaf.iter().copied().max().unwrap_or(0).max(1) as f32;
+    let width = (32.0 + BAR_WIDTH * stats.entries_per_leaf.len() as f32).max(180.0);
+
+    // Render a histogram of entries per leaf below the counters
+    let bars = stats.entries_per_leaf.iter().enumerate().map(|(entries, &leaves)| {
+        let height = BAR_MAX_HEIGHT * leaves as f32 / max_leaves;
+        let x = format!("{:.3}", 16.0 + BAR_WIDTH * entries as f32);
+        let y = format!("{:.3}", 98.0 + BAR_MAX_HEIGHT - height);
+
+        html! {
+            <rect x={x} y={y} width={(BAR_WIDTH - 2.0).to_string()} height={format!("{height:.3}")} fill="gray">
+                <title>{format!("{leaves} leaves with {entries} entries")}</title>
+            </rect>
+        }
+    }).collect::<Html>();
+
+    html! {
+        <svg id="qtree-stats" x="10" y="10">
+            <rect x="0" y="0" width={width.to_string()} height="190" fill="dark-gray" opacity="0.75" rx="15" />
+
+            <text x="16" y="26" font-weight="bold" fill="white">
+                {format!("Depth: {}", stats.depth)}
+            </text>
+
+            <text x="16" y="44" font-weight="bold" fill="white">
+                {format!("Nodes: {} ({} leaves)", stats.node_count, stats.leaf_count)}
+            </text>
+
+            <text x="16" y="62" font-weight="bold" fill="white">
+                {format!("Entries: {}", stats.entry_count)}
+            </text>
+
+            <text x="16" y="86" fill="white" font-size="12">
+                { "Entries per leaf" }
+            </text>
+
+            { bars }
+
+            <text x="16" y="176" fill="white" font-size="12">
+                {format!("0 .. {}", stats.entries_per_leaf.len().saturating_sub(1))}
+            </text>
+        </svg>
+    }
+}
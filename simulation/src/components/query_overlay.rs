@@ -0,0 +1,40 @@
+//!
+//! This component renders the search of the head quadtree made for a cluster head
+//! candidate in the last clustering round: the searched region, the nodes visited
+//! and the heads found
+//!
+
+use yew::prelude::*;
+use crate::cluster::HeadQuery;
+
+const REGION_COLOR: &str = "yellow";
+const VISITED_COLOR: &str = "orange";
+const FOUND_COLOR: &str = "cyan";
+
+pub fn render(query: &HeadQuery) -> Html {
+    let HeadQuery { center, radius, visited, found } = query;
+
+    let cx = format!("{:.3}", center.x);
+    let cy = format!("{:.3}", center.y);
+
+    html! {
+        <g>
+            // Nodes the search descended into
+            { visited.iter().map(|node| node.render_with_stroke(VISITED_COLOR)).collect::<Html>() }
+
+            // Region searched around the candidate
+            <circle cx={cx} cy={cy} r={format!("{radius:.3}")} fill="none" stroke={REGION_COLOR} stroke-width="1" stroke-dasharray="4 4" />
+
+            // Heads returned by the search
+            { found.iter().map(|point| {
+                let x = format!("{:.3}", point.x);
+                let y = format!("{:.3}", point.y);
+                html! { <circle cx={x} cy={y} r="8" fill="none" stroke={FOUND_COLOR} stroke-width="1.5" /> }
+            }).collect::<Html>() }
+
+            <text x={format!("{:.3}", center.x + radius + 4.0)} y={format!("{:.3}", center.y)} fill={REGION_COLOR} font-size="12">
+                {format!("{} heads found, {} nodes visited", found.len(), visited.len())}
+            </text>
+        </g>
+    }
+}
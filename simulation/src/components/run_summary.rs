@@ -0,0 +1,46 @@
+//!
+//! This component renders the fairness and lifetime metrics of a run once it is stopped
+//!
+
+use yew::prelude::*;
+use crate::metrics::Summary;
+
+fn format_round(round: Option<u64>) -> String {
+    round.map(|r| format!("round {r}")).unwrap_or_else(|| "not reached".to_string())
+}
+
+pub fn render(summary: &Summary) -> Html {
+    html! {
+        <div class="summary">
+            <h3 class="summary__title">{ format!("Run summary after {} rounds", summary.rounds) }</h3>
+            <table>
+                <tr>
+                    <td>{ "Energy fairness (Jain)" }</td>
+                    <td>{ format!("{:.3}", summary.energy_fairness) }</td>
+                </tr>
+                <tr>
+                    <td>{ "Relay fairness (Jain)" }</td>
+                    <td>{ format!("{:.3}", summary.relay_fairness) }</td>
+                </tr>
+                <tr>
+                    <td>{ "First satellite depleted" }</td>
+                    <td>{ format_round(summary.first_depletion) }</td>
+                </tr>
+                <tr>
+                    <td>{ "Half of satellites depleted" }</td>
+                    <td>{ format_round(summary.half_depleted) }</td>
+                </tr>
+                <tr>
+                    <td>{ "Mean rounds in safe mode" }</td>
+                    <td>{ format!("{:.1}", summary.mean_safe_mode_rounds) }</td>
+                </tr>
+                { summary.most_safe_mode.iter().map(|(id, rounds)| html! {
+                    <tr>
+                        <td class="summary__detail">{ format!("Satellite {id} in safe mode") }</td>
+                        <td>{ format!("{rounds} rounds") }</td>
+                    </tr>
+                }).collect::<Html>() }
+            </table>
+        </div>
+    }
+}
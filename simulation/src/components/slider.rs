@@ -1,6 +1,7 @@
 use std::cell::Cell;
 
 use web_sys::HtmlInputElement;
+use crate::settings::{SettingsProblem, Severity};
 use yew::events::InputEvent;
 use yew::{html, Callback, Component, Context, Html, Properties, TargetCast};
 
@@ -25,6 +26,9 @@ pub struct Props {
     pub max: f32,
     #[prop_or_default]
     pub step: Option<f32>,
+    /// Validation problems shown under the slider
+    #[prop_or_default]
+    pub problems: Vec<SettingsProblem>,
 }
 
 pub struct Slider {
@@ -54,6 +58,7 @@ impl Component for Slider {
             min,
             max,
             step,
+            ref problems,
         } = *ctx.props();
 
         let precision = precision.unwrap_or_else(|| usize::from(percentage));
@@ -86,7 +91,18 @@ impl Component for Slider {
                     {oninput}
                 />
                 <span class="slider__value">{ display_value }</span>
+                { problems.iter().map(render_problem).collect::<Html>() }
             </div>
         }
     }
 }
+
+pub fn render_problem(problem: &SettingsProblem) -> Html {
+    let class = match problem.severity {
+        Severity::Error => "problem problem--error",
+        Severity::Warning => "problem problem--warning",
+    };
+    html! {
+        <div {class}>{ problem.message.clone() }</div>
+    }
+}
@@ -0,0 +1,29 @@
+//! This module talks to the Tauri desktop application when the simulation is
+//! hosted by it. Its commands are called through the global `window.__TAURI__`
+//! object, enabled by `withGlobalTauri` in the Tauri configuration.
+
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(catch, js_namespace = ["window", "__TAURI__", "tauri"], js_name = invoke)]
+    fn tauri_invoke(cmd: &str, args: JsValue) -> Result<JsValue, JsValue>;
+}
+
+/// Whether the page is hosted by the Tauri desktop application
+pub fn in_tauri() -> bool {
+    js_sys::Reflect::has(&gloo::utils::window(), &JsValue::from_str("__TAURI__")).unwrap_or(false)
+}
+
+/// Call the command `cmd` of the desktop application with string arguments,
+/// resolving to what the command returned
+pub async fn invoke(cmd: &str, args: &[(&str, &str)]) -> Result<JsValue, JsValue> {
+    let object = js_sys::Object::new();
+    for (key, value) in args {
+        js_sys::Reflect::set(&object, &JsValue::from_str(key), &JsValue::from_str(value))?;
+    }
+
+    let promise = tauri_invoke(cmd, object.into())?;
+    JsFuture::from(js_sys::Promise::from(promise)).await
+}
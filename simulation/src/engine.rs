@@ -0,0 +1,673 @@
+//! This module runs a simulation without any interface. The motion of the
+//! satellites, the clustering rounds and the game rounds are scheduled by
+//! simulated time, so the interface only decides how far to run on every frame
+//! and headless runs go as fast as possible with the same results.
+
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::clock::SimClock;
+use crate::cluster::{self, Cluster, ClusterMap, HeadQuery};
+use crate::events::{EventRecord, SimEvent};
+use crate::metrics::{Report, Sample, SatelliteSample};
+use crate::quadtree::{box2d::Box2d, quadtree::QuadTree, types::*};
+use crate::satellite::{EnergyChange, SatelliteEnergy, SatellitePosition, SatelliteProperties};
+use crate::scenario::Scenario;
+use crate::scheduler::Scheduler;
+use crate::snapshot::{Snapshot, SnapshotError, SNAPSHOT_VERSION};
+use crate::simulation::SIZE;
+
+/// Simulated seconds between updates of the satellite positions
+pub const MOTION_STEP_S: f64 = 10.0;
+
+/// What happens at a scheduled time, activities due together run in this order.
+/// Bundle arrivals and the start and end of contacts with ground stations depend
+/// on routing, which isn't simulated yet, so they aren't scheduled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+pub enum Activity {
+    Motion,
+    Clustering,
+    Game,
+}
+
+/// Condition ending a run started with `Engine::run_until_stop`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stop {
+    /// Simulated seconds since the epoch
+    Time(f64),
+    /// More than this many satellites are depleted
+    Depleted(usize),
+    /// Satellites relayed for their cluster at least this many times since the start of the run
+    Relays(u64),
+}
+
+pub struct Engine {
+    scenario: Scenario,
+    entity_props: Vec<SatelliteProperties>,
+    entity_positions: Vec<SatellitePosition>,
+    entity_energy: Vec<SatelliteEnergy>,
+    game_round: u64,
+    clock: SimClock,
+    scheduler: Scheduler<Activity>,
+    qtree: QuadTree<f32, usize>,
+    head_tree: QuadTree<f32, usize>,
+    /// Searches of `head_tree` made in the last clustering round, indexed by satellite id
+    head_queries: Vec<Option<HeadQuery>>,
+    cluster_map: ClusterMap,
+    /// Events since the last report
+    events: Vec<EventRecord>,
+    /// Seeded from the scenario so a run can be repeated
+    rng: ChaCha8Rng,
+}
+
+impl Engine {
+    pub fn new(scenario: Scenario) -> Self {
+        let mut rng = scenario.rng();
+        let mut qtree = screen_quadtree();
+        let (entity_props, entity_positions, entity_energy) = spawn_satellites(&scenario, &mut rng, &mut qtree);
+
+        let mut scheduler = Scheduler::new();
+        scheduler.schedule(MOTION_STEP_S, Activity::Motion);
+        scheduler.schedule(scenario.settings.comms_interval_s as f64, Activity::Clustering);
+        scheduler.schedule(scenario.settings.game_interval_s as f64, Activity::Game);
+
+        Self {
+            clock: SimClock::new(scenario.epoch),
+            scenario,
+            entity_props,
+            entity_positions,
+            entity_energy,
+            game_round: 0,
+            scheduler,
+            qtree,
+            head_tree: screen_quadtree(),
+            head_queries: Vec::new(),
+            cluster_map: ClusterMap::new(),
+            events: Vec::new(),
+            rng,
+        }
+    }
+
+    /// Continue a run from a snapshot, nothing is selected. Fails when the snapshot doesn't
+    /// pass `Snapshot::validate`.
+    pub fn from_snapshot(snapshot: Snapshot) -> Result<Self, SnapshotError> {
+        snapshot.validate()?;
+        let Snapshot {
+            scenario,
+            game_round,
+            clock,
+            scheduler,
+            mut props,
+            positions,
+            energy,
+            cluster_map,
+            events,
+            rng,
+            ..
+        } = snapshot;
+
+        for properties in &mut props {
+            properties.set_selected(false);
+        }
+
+        // The quadtrees only index the positions, so they are rebuilt instead of saved
+        let mut qtree = screen_quadtree();
+        for (id, position) in positions.iter().enumerate() {
+            qtree.insert(Point::from(position.screen_position()), id);
+        }
+
+        Ok(Self {
+            scenario,
+            entity_props: props,
+            entity_positions: positions,
+            entity_energy: energy,
+            game_round,
+            clock,
+            scheduler,
+            qtree,
+            head_tree: screen_quadtree(),
+            head_queries: Vec::new(),
+            cluster_map,
+            events,
+            rng,
+        })
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            scenario: self.scenario.clone(),
+            game_round: self.game_round,
+            clock: self.clock.clone(),
+            scheduler: self.scheduler.clone(),
+            props: self.entity_props.clone(),
+            positions: self.entity_positions.clone(),
+            energy: self.entity_energy.clone(),
+            cluster_map: self.cluster_map.clone(),
+            events: self.events.clone(),
+            rng: self.rng.clone(),
+        }
+    }
+
+    pub fn scenario(&self) -> &Scenario {
+        &self.scenario
+    }
+
+    pub fn props(&self) -> &[SatelliteProperties] {
+        &self.entity_props
+    }
+
+    pub fn positions(&self) -> &[SatellitePosition] {
+        &self.entity_positions
+    }
+
+    pub fn energy(&self) -> &[SatelliteEnergy] {
+        &self.entity_energy
+    }
+
+    pub fn cluster_map(&self) -> &ClusterMap {
+        &self.cluster_map
+    }
+
+    pub fn qtree(&self) -> &QuadTree<f32, usize> {
+        &self.qtree
+    }
+
+    /// Search for nearby heads made when `id` was a head candidate in the last clustering round
+    pub fn head_query(&self, id: usize) -> Option<&HeadQuery> {
+        self.head_queries.get(id)?.as_ref()
+    }
+
+    pub fn clock(&self) -> &SimClock {
+        &self.clock
+    }
+
+    pub fn game_round(&self) -> u64 {
+        self.game_round
+    }
+
+    /// Times the satellites relayed for their cluster since the start of the run
+    pub fn relay_count(&self) -> u64 {
+        self.entity_energy.iter().map(|energy| energy.relay_count() as u64).sum()
+    }
+
+    pub fn set_selected(&mut self, id: usize, selected: bool) {
+        self.entity_props[id].set_selected(selected);
+    }
+
+    /// Whether the scenario ran for its whole duration
+    pub fn finished(&self) -> bool {
+        self.scenario.finished(self.game_round)
+    }
+
+    /// Run the next scheduled activity, returning the report when it was a game round
+    pub fn step(&mut self) -> Option<Report> {
+        if self.finished() {
+            return None;
+        }
+        let (time, activity) = self.scheduler.pop()?;
+        self.run(time, activity)
+    }
+
+    /// Run the activities due until `time` simulated seconds, but at most `max_activities` of them so
+    /// a large time warp can't stall the page. Returns whether the run reached `time` and moved the clock
+    /// there. Otherwise the clock stays at the last activity, and the rest runs on the next call unless
+    /// the scenario is finished.
+    pub fn run_until(&mut self, time: f64, max_activities: usize, mut on_report: impl FnMut(Report)) -> bool {
+        for _ in 0..max_activities {
+            if self.finished() {
+                return false;
+            }
+            let Some((due, activity)) = self.scheduler.pop_until(time) else {
+                break;
+            };
+            if let Some(report) = self.run(due, activity) {
+                on_report(report);
+            }
+        }
+
+        if self.finished() || self.scheduler.next_time().is_some_and(|next| next <= time) {
+            return false;
+        }
+        self.clock.advance_to(time);
+        true
+    }
+
+    /// Run the activities due before the next `activity` and that activity itself,
+    /// e.g. up to and including the next game round
+    pub fn step_until(&mut self, activity: Activity, mut on_report: impl FnMut(Report)) {
+        while !self.finished() {
+            let Some((_, &next)) = self.scheduler.peek() else {
+                break;
+            };
+            if let Some(report) = self.step() {
+                on_report(report);
+            }
+            if next == activity {
+                break;
+            }
+        }
+    }
+
+    /// Run as fast as possible until `stop`, but at most `max_activities` activities.
+    /// Returns whether the run stopped, either at `stop` or at the end of the scenario.
+    pub fn run_until_stop(&mut self, stop: Stop, max_activities: usize, on_report: impl FnMut(Report)) -> bool {
+        match stop {
+            Stop::Time(time) => self.run_until(time, max_activities, on_report) || self.finished(),
+            Stop::Depleted(count) => self.step_until_reached(max_activities, on_report, |engine| engine.depleted_count() > count),
+            Stop::Relays(count) => self.step_until_reached(max_activities, on_report, |engine| engine.relay_count() >= count),
+        }
+    }
+
+    /// Step through at most `max_activities` activities until `reached` or the end of the
+    /// scenario, returning whether either happened
+    fn step_until_reached(
+        &mut self,
+        max_activities: usize,
+        mut on_report: impl FnMut(Report),
+        reached: impl Fn(&Self) -> bool,
+    ) -> bool {
+        for _ in 0..max_activities {
+            if self.finished() || reached(self) {
+                return true;
+            }
+            if let Some(report) = self.step() {
+                on_report(report);
+            }
+        }
+        self.finished() || reached(self)
+    }
+
+    pub fn depleted_count(&self) -> usize {
+        self.entity_energy.iter().filter(|energy| energy.depleted()).count()
+    }
+
+    /// Run until the scenario is finished as fast as possible, the scenario needs a duration
+    pub fn run_to_end(&mut self, mut on_report: impl FnMut(Report)) {
+        while !self.finished() {
+            match self.step() {
+                Some(report) => on_report(report),
+                None if self.scheduler.is_empty() => break,
+                None => (),
+            }
+        }
+    }
+
+    fn run(&mut self, time: f64, activity: Activity) -> Option<Report> {
+        self.clock.advance_to(time);
+        let settings = &self.scenario.settings;
+        let (comms_interval, game_interval) = (settings.comms_interval_s as f64, settings.game_interval_s as f64);
+
+        match activity {
+            Activity::Motion => {
+                self.scheduler.schedule(time + MOTION_STEP_S, Activity::Motion);
+                self.move_satellites(MOTION_STEP_S as f32);
+                None
+            }
+            Activity::Clustering => {
+                self.scheduler.schedule(time + comms_interval, Activity::Clustering);
+                self.cluster_round();
+                None
+            }
+            Activity::Game => {
+                self.scheduler.schedule(time + game_interval, Activity::Game);
+                Some(self.play_game_round())
+            }
+        }
+    }
+
+    /// Move the satellites along their orbits for `dt` simulated seconds and in the quadtree
+    fn move_satellites(&mut self, dt: f32) {
+        for (id, (pos, props)) in self
+            .entity_positions
+            .iter_mut()
+            .zip(self.entity_props.iter())
+            .enumerate()
+        {
+            let old_position = pos.screen_position();
+            pos.update(props, dt);
+            let new_position = pos.screen_position();
+
+            let moved = self.qtree.update(&Point::from(old_position), Point::from(new_position), id);
+            debug_assert!(moved, "satellite {id} left the quadtree at {new_position:?}");
+        }
+    }
+
+    /// Elect cluster heads among the satellites with enough energy and assign the others to the nearest head
+    fn cluster_round(&mut self) {
+        let settings = &self.scenario.settings;
+
+        // Iterate through all satellites to create a list of cluster head candidates based on energy if exceeds threshold
+
+        let cluster_head_candidates = self
+            .entity_energy
+            .iter()
+            .enumerate()
+            .filter_map(|(id, energy)| {
+                if energy.energy() > settings.energy_threshold {
+                    Some(id)
+                } else {
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // Consolidate cluster heads based on distance
+        let cluster_heads = cluster::elect_heads(
+            cluster_head_candidates,
+            &self.entity_positions,
+            settings.cluster_distance,
+            &mut self.head_tree,
+            &mut self.head_queries,
+        );
+
+        let mut is_head = vec![false; self.entity_positions.len()];
+        for &id in &cluster_heads {
+            is_head[id] = true;
+        }
+
+        // Create edge list of members to their nearest cluster heads
+        let mut clusters = ClusterMap::new();
+
+        // Create clusters using cluster heads
+        for ch_id in cluster_heads.iter() {
+            let mut cluster = Cluster::new(*ch_id);
+            if let Some(prev_cluster) = self.cluster_map.get(cluster.head()) {
+                cluster.set_color(prev_cluster.color());
+                self.entity_props[cluster.head()].set_color(prev_cluster.color());
+            } else {
+                cluster.set_color(self.entity_props[cluster.head()].color());
+            }
+            clusters.insert(cluster);
+        }
+
+        // Assign members to the nearest cluster head
+        for prop in self.entity_props.iter() {
+            let id = prop.id();
+            if is_head[id] {
+                // skip assignment for cluster heads
+                continue;
+            }
+
+            let position = self.entity_positions[id].screen_position();
+            let nearest_head = self.head_tree.nearest(&Point::from(position));
+
+            if let Some((head, _)) = nearest_head {
+                let cluster = clusters.get_mut(*head.value).unwrap();
+                cluster.add_member(id);
+            }
+        }
+
+        // Set cluster colors to the average color of all members
+        for cluster in clusters.clusters_mut() {
+            if cluster.size() < 2 {
+                continue;
+            }
+
+            // Mix member colors
+            let mut member_color = cluster.members().iter().map(|id| self.entity_props[*id].color()).sum::<f32>();
+            member_color /= cluster.members().len() as f32;
+            let head_color = cluster.color();
+            let mut color = (head_color + member_color) / 2.0;
+            color %= 360.0;
+
+            // Set color
+            cluster.set_color(color);
+            self.entity_props[cluster.head()].set_color(color);
+            for member in cluster.members() {
+                self.entity_props[*member].set_color(color);
+            }
+        }
+
+        // Record the satellites that became heads or moved to another cluster
+        let previous = self.cluster_map.membership(self.entity_props.len());
+        let current = clusters.membership(self.entity_props.len());
+        for (satellite, (&before, &after)) in previous.iter().zip(&current).enumerate() {
+            if before == after {
+                continue;
+            }
+            let event = match after {
+                Some(head) if head == satellite => SimEvent::BecameHead { satellite },
+                Some(cluster) => SimEvent::JoinedCluster { satellite, cluster },
+                None => continue,
+            };
+            self.events.push(EventRecord { round: self.game_round, event });
+        }
+
+        self.cluster_map = clusters;
+    }
+
+    /// Play one round of the relay game in every cluster and report the metrics
+    fn play_game_round(&mut self) -> Report {
+        let mut recharge_events = 0;
+        self.game_round += 1;
+
+        for cluster in self.cluster_map.clusters() {
+            if cluster.size() < 2 {
+                continue;
+            }
+
+            for &id in cluster.members() {
+                let sat = self.entity_energy.get_mut(id).expect("Couldn't get sat in cluster");
+                sat.update_game(cluster, &mut self.rng);
+
+                let (satellite, cluster, prob_entering) = (id, cluster.head(), sat.prob_entering());
+                let event = if sat.in_game() {
+                    SimEvent::EnteredGame { satellite, cluster, prob_entering }
+                } else {
+                    SimEvent::Declined { satellite, cluster, prob_entering }
+                };
+                self.events.push(EventRecord { round: self.game_round, event });
+            }
+
+            // All sats in cluster should've made a decision to enter or leave, and paying
+            // or collecting the payoff doesn't change it
+            let in_game = cluster.members().iter().filter(|&&id| self.entity_energy[id].in_game()).count();
+
+            for &id in cluster.members() {
+                let sat = &mut self.entity_energy[id];
+                let neighbors_in_game = in_game - usize::from(sat.in_game());
+                let change = sat.update(self.game_round, neighbors_in_game);
+                let (satellite, cluster, energy) = (id, cluster.head(), sat.energy());
+                let event = match change {
+                    EnergyChange::Consumed => Some(SimEvent::Consumed { satellite, cluster, energy }),
+                    EnergyChange::Recharged => {
+                        recharge_events += 1;
+                        Some(SimEvent::Recharged { satellite, cluster, energy })
+                    }
+                    EnergyChange::Unchanged => None,
+                };
+                if let Some(event) = event {
+                    self.events.push(EventRecord { round: self.game_round, event });
+                }
+            }
+        }
+
+        Report {
+            sample: Sample::new(self.game_round, &self.entity_energy, &self.cluster_map, recharge_events),
+            satellites: SatelliteSample::all(
+                self.game_round,
+                &self.entity_props,
+                &self.entity_positions,
+                &self.entity_energy,
+                &self.cluster_map,
+            ),
+            events: std::mem::take(&mut self.events),
+        }
+    }
+}
+
+/// Create the satellites of a scenario and insert them in `qtree`, the vectors are indexed by satellite id
+fn spawn_satellites(
+    scenario: &Scenario,
+    rng: &mut ChaCha8Rng,
+    qtree: &mut QuadTree<f32, usize>,
+) -> (Vec<SatelliteProperties>, Vec<SatellitePosition>, Vec<SatelliteEnergy>) {
+    let definitions = scenario.satellite_definitions();
+
+    let mut entity_props = Vec::with_capacity(definitions.len());
+    let mut entity_positions = Vec::with_capacity(definitions.len());
+    let mut entity_energy = Vec::with_capacity(definitions.len());
+
+    for (id, definition) in definitions.iter().enumerate() {
+        let properties = SatelliteProperties::new(id, definition, rng.gen::<f32>() * 360.0);
+        let position = SatellitePosition::new(&properties, definition.angle);
+        let game = SatelliteEnergy::new(id, definition, &scenario.settings);
+
+        qtree.insert(Point::from(position.screen_position()), id);
+
+        entity_props.push(properties);
+        entity_positions.push(position);
+        entity_energy.push(game);
+    }
+
+    (entity_props, entity_positions, entity_energy)
+}
+
+/// Empty quadtree covering the whole screen
+fn screen_quadtree() -> QuadTree<f32, usize> {
+    QuadTree::new(
+        Box2d::new(Point::new(0.0, SIZE.y), Point::new(SIZE.x, 0.0)),
+        4,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::Metrics;
+    use crate::scenario::Strategy;
+    use crate::settings::Settings;
+
+    fn scenario() -> Scenario {
+        let mut scenario = Scenario::new(Settings { boids: 30, ..Default::default() }, 7);
+        scenario.duration = Some(20);
+        scenario
+    }
+
+    #[test]
+    fn runs_repeat_whatever_the_frame_rate() {
+        let mut headless = Vec::new();
+        let mut engine = Engine::new(scenario());
+        engine.run_to_end(|report| headless.push(report));
+        assert_eq!(headless.len(), 20);
+        assert!(engine.finished());
+
+        // Short frames with a small budget, as the interface would run it
+        let mut framed = Vec::new();
+        let mut engine = Engine::new(scenario());
+        while !engine.finished() {
+            let until = engine.clock().elapsed_secs() + 250.0;
+            engine.run_until(until, 8, |report| framed.push(report));
+        }
+        assert_eq!(framed, headless);
+    }
+
+    #[test]
+    fn steps_and_stops_at_conditions() {
+        let mut engine = Engine::new(Scenario { duration: None, ..scenario() });
+        let mut reports = Vec::new();
+
+        engine.step_until(Activity::Game, |report| reports.push(report));
+        assert_eq!((reports.len(), engine.game_round()), (1, 1));
+        assert_eq!(engine.clock().elapsed_secs(), engine.scenario().settings.game_interval_s as f64);
+
+        engine.step_until(Activity::Motion, |report| reports.push(report));
+        assert_eq!(reports.len(), 1);
+
+        assert!(engine.run_until_stop(Stop::Time(5000.0), usize::MAX, |report| reports.push(report)));
+        assert_eq!(engine.clock().elapsed_secs(), 5000.0);
+
+        // A capped run stops at the last activity it ran until a later call reaches the time
+        assert!(!engine.run_until(6000.0, 1, |report| reports.push(report)));
+        assert!(engine.clock().elapsed_secs() < 6000.0);
+        while !engine.run_until(6000.0, 1, |report| reports.push(report)) {}
+        assert_eq!(engine.clock().elapsed_secs(), 6000.0);
+
+        // Relays are counted over every satellite and game round
+        let mut engine = Engine::new(Scenario { duration: None, ..scenario() });
+        let mut relays = 0;
+        assert!(engine.run_until_stop(Stop::Relays(50), 100_000, |report| {
+            relays += report.events.iter().filter(|r| matches!(r.event, SimEvent::Consumed { .. })).count() as u64;
+        }));
+        assert!(engine.relay_count() >= 50);
+        assert_eq!(engine.relay_count(), relays);
+
+        // Satellites that always relay spend all their energy
+        let mut scenario = Scenario { duration: None, ..scenario() };
+        scenario.settings.energy_threshold = 0.5;
+        scenario.satellites = scenario.satellite_definitions();
+        for satellite in &mut scenario.satellites {
+            satellite.strategy = Strategy::AlwaysRelay;
+            satellite.initial_energy = 10.0;
+        }
+        let mut engine = Engine::new(scenario);
+        assert!(engine.run_until_stop(Stop::Depleted(0), 100_000, |_| ()));
+        assert!(engine.depleted_count() > 0);
+    }
+
+    #[test]
+    fn records_the_head_searches_of_the_candidates() {
+        let mut engine = Engine::new(scenario());
+        let clustered = engine.scenario().settings.comms_interval_s as f64;
+        engine.run_until(clustered, 1000, |_| {});
+
+        let settings = &engine.scenario().settings;
+        for (id, energy) in engine.energy().iter().enumerate() {
+            let Some(query) = engine.head_query(id) else {
+                assert!(energy.energy() <= settings.energy_threshold);
+                continue;
+            };
+            assert_eq!(query.radius, settings.cluster_distance);
+            assert!(query.visited.iter().all(|node| node.distance_squared(&query.center) <= (query.radius * query.radius) as f64));
+
+            // Heads are elected only when their search found no other head
+            let is_head = engine.cluster_map().get(id).is_some();
+            assert_eq!(is_head, query.found.is_empty());
+        }
+        assert!(!engine.cluster_map().clusters().is_empty());
+        assert!(engine.cluster_map().clusters().iter().all(|cluster| engine.head_query(cluster.head()).is_some()));
+    }
+
+    #[test]
+    fn depletion_is_reached_above_zero() {
+        // Without recharges safe mode keeps the leftover half of the cost forever
+        let mut scenario = Scenario { duration: None, ..scenario() };
+        scenario.settings.energy_threshold = 0.5;
+        scenario.settings.energy_gain = 0.0;
+        scenario.satellites = scenario.satellite_definitions();
+        for satellite in &mut scenario.satellites {
+            satellite.strategy = Strategy::AlwaysRelay;
+            satellite.initial_energy = 10.5;
+        }
+        let count = scenario.satellites.len();
+
+        let mut metrics = Metrics::new();
+        let mut engine = Engine::new(scenario);
+        assert!(engine.run_until_stop(Stop::Depleted(count / 2), 100_000, |report| metrics.record_report(report)));
+        assert!(engine.energy().iter().filter(|e| e.depleted()).all(|e| e.energy() == 0.5));
+
+        let summary = metrics.summary().unwrap();
+        let (first, half) = (summary.first_depletion.unwrap(), summary.half_depleted.unwrap());
+        assert!(first <= half && half <= engine.game_round());
+    }
+
+    #[test]
+    fn restored_snapshots_continue_the_run() {
+        let mut engine = Engine::new(scenario());
+        for _ in 0..7 {
+            engine.step_until(Activity::Game, |_| ());
+        }
+
+        let snapshot = engine.snapshot();
+        let restored = Snapshot::parse(&snapshot.to_json()).unwrap();
+        assert_eq!(restored, snapshot);
+
+        let (mut original, mut branched) = (Vec::new(), Vec::new());
+        engine.run_to_end(|report| original.push(report));
+        Engine::from_snapshot(restored).unwrap().run_to_end(|report| branched.push(report));
+        assert_eq!(original.len(), 13);
+        assert_eq!(branched, original);
+    }
+}
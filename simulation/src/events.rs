@@ -0,0 +1,231 @@
+//! This module records what the satellites did during a run as structured
+//! events, so the decisions of the game and the changes in the clusters can
+//! be inspected and filtered instead of being written to the console.
+
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+/// Something a satellite did during a run. Clusters are identified by the id
+/// of their head.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SimEvent {
+    EnteredGame { satellite: usize, cluster: usize, prob_entering: f32 },
+    Declined { satellite: usize, cluster: usize, prob_entering: f32 },
+    /// Energy left after relaying
+    Consumed { satellite: usize, cluster: usize, energy: f32 },
+    /// Energy after being paid by the neighbors that relayed
+    Recharged { satellite: usize, cluster: usize, energy: f32 },
+    BecameHead { satellite: usize },
+    JoinedCluster { satellite: usize, cluster: usize },
+}
+
+/// Type of a [`SimEvent`], without its data
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EventKind {
+    EnteredGame,
+    Declined,
+    Consumed,
+    Recharged,
+    BecameHead,
+    JoinedCluster,
+}
+
+impl EventKind {
+    pub const ALL: [EventKind; 6] = [
+        EventKind::EnteredGame,
+        EventKind::Declined,
+        EventKind::Consumed,
+        EventKind::Recharged,
+        EventKind::BecameHead,
+        EventKind::JoinedCluster,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            EventKind::EnteredGame => "entered game",
+            EventKind::Declined => "declined",
+            EventKind::Consumed => "consumed",
+            EventKind::Recharged => "recharged",
+            EventKind::BecameHead => "became head",
+            EventKind::JoinedCluster => "joined cluster",
+        }
+    }
+}
+
+impl SimEvent {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            SimEvent::EnteredGame { .. } => EventKind::EnteredGame,
+            SimEvent::Declined { .. } => EventKind::Declined,
+            SimEvent::Consumed { .. } => EventKind::Consumed,
+            SimEvent::Recharged { .. } => EventKind::Recharged,
+            SimEvent::BecameHead { .. } => EventKind::BecameHead,
+            SimEvent::JoinedCluster { .. } => EventKind::JoinedCluster,
+        }
+    }
+
+    pub fn satellite(&self) -> usize {
+        match *self {
+            SimEvent::EnteredGame { satellite, .. }
+            | SimEvent::Declined { satellite, .. }
+            | SimEvent::Consumed { satellite, .. }
+            | SimEvent::Recharged { satellite, .. }
+            | SimEvent::BecameHead { satellite }
+            | SimEvent::JoinedCluster { satellite, .. } => satellite,
+        }
+    }
+
+    /// Head of the cluster the event happened in, a new head leads its own cluster
+    pub fn cluster(&self) -> usize {
+        match *self {
+            SimEvent::EnteredGame { cluster, .. }
+            | SimEvent::Declined { cluster, .. }
+            | SimEvent::Consumed { cluster, .. }
+            | SimEvent::Recharged { cluster, .. }
+            | SimEvent::JoinedCluster { cluster, .. } => cluster,
+            SimEvent::BecameHead { satellite } => satellite,
+        }
+    }
+
+    /// Value carried by the event, if any, as shown in the log
+    pub fn value(&self) -> Option<f32> {
+        match *self {
+            SimEvent::EnteredGame { prob_entering, .. } | SimEvent::Declined { prob_entering, .. } => {
+                Some(prob_entering)
+            }
+            SimEvent::Consumed { energy, .. } | SimEvent::Recharged { energy, .. } => Some(energy),
+            SimEvent::BecameHead { .. } | SimEvent::JoinedCluster { .. } => None,
+        }
+    }
+}
+
+/// An event and the game round it happened in
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct EventRecord {
+    pub round: u64,
+    #[serde(flatten)]
+    pub event: SimEvent,
+}
+
+/// Selects the events matching every field that is set
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct EventFilter {
+    pub satellite: Option<usize>,
+    pub cluster: Option<usize>,
+    pub kind: Option<EventKind>,
+}
+
+impl EventFilter {
+    pub fn matches(&self, event: &SimEvent) -> bool {
+        self.satellite.is_none_or(|id| event.satellite() == id)
+            && self.cluster.is_none_or(|id| event.cluster() == id)
+            && self.kind.is_none_or(|kind| event.kind() == kind)
+    }
+}
+
+/// The most recent events of a run, oldest first
+#[derive(Clone, Debug, PartialEq)]
+pub struct EventLog {
+    records: VecDeque<EventRecord>,
+    capacity: usize,
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+impl EventLog {
+    /// Events kept before the oldest ones are dropped
+    pub const DEFAULT_CAPACITY: usize = 50_000;
+
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            records: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    pub fn record(&mut self, record: EventRecord) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.records.len() == self.capacity {
+            self.records.pop_front();
+        }
+        self.records.push_back(record);
+    }
+
+    pub fn extend(&mut self, records: impl IntoIterator<Item = EventRecord>) {
+        for record in records {
+            self.record(record);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.records.clear();
+    }
+
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    pub fn records(&self) -> impl DoubleEndedIterator<Item = &EventRecord> {
+        self.records.iter()
+    }
+
+    /// Events matching `filter`, oldest first
+    pub fn filtered<'a>(&'a self, filter: &'a EventFilter) -> impl DoubleEndedIterator<Item = &'a EventRecord> + 'a {
+        self.records.iter().filter(move |r| filter.matches(&r.event))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn log() -> EventLog {
+        let mut log = EventLog::default();
+        log.extend([
+            EventRecord { round: 0, event: SimEvent::BecameHead { satellite: 1 } },
+            EventRecord { round: 0, event: SimEvent::JoinedCluster { satellite: 2, cluster: 1 } },
+            EventRecord { round: 1, event: SimEvent::EnteredGame { satellite: 2, cluster: 1, prob_entering: 0.4 } },
+            EventRecord { round: 1, event: SimEvent::Declined { satellite: 1, cluster: 1, prob_entering: 0.2 } },
+            EventRecord { round: 1, event: SimEvent::Consumed { satellite: 2, cluster: 1, energy: 90.0 } },
+            EventRecord { round: 1, event: SimEvent::Recharged { satellite: 1, cluster: 1, energy: 110.0 } },
+        ]);
+        log
+    }
+
+    #[test]
+    fn filters_by_satellite_cluster_and_kind() {
+        let log = log();
+        let count = |filter: EventFilter| log.filtered(&filter).count();
+
+        assert_eq!(count(EventFilter::default()), 6);
+        assert_eq!(count(EventFilter { satellite: Some(2), ..Default::default() }), 3);
+        assert_eq!(count(EventFilter { cluster: Some(1), ..Default::default() }), 6);
+        assert_eq!(count(EventFilter { cluster: Some(2), ..Default::default() }), 0);
+        assert_eq!(count(EventFilter { kind: Some(EventKind::Consumed), ..Default::default() }), 1);
+        assert_eq!(
+            count(EventFilter { satellite: Some(1), kind: Some(EventKind::Declined), ..Default::default() }),
+            1
+        );
+    }
+
+    #[test]
+    fn serializes_with_event_tag() {
+        let record = EventRecord { round: 3, event: SimEvent::BecameHead { satellite: 7 } };
+        let json = serde_json::to_string(&record).unwrap();
+        assert_eq!(json, r#"{"round":3,"event":"became_head","satellite":7}"#);
+        assert_eq!(serde_json::from_str::<EventRecord>(&json).unwrap(), record);
+    }
+}
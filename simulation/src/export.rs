@@ -0,0 +1,215 @@
+//! This module serializes the metrics of a run to CSV and JSON Lines so they
+//! can be analysed outside of the simulation. Files are saved to disk through
+//! the desktop application when running inside Tauri, and downloaded by the
+//! browser otherwise.
+
+use std::fmt::Write;
+
+use gloo::file::{Blob, ObjectUrl};
+use gloo::timers::callback::Timeout;
+use serde::Serialize;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::spawn_local;
+use web_sys::HtmlAnchorElement;
+
+use crate::desktop;
+use crate::events::EventRecord;
+use crate::metrics::{Sample, SatelliteSample};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Csv,
+    JsonLines,
+}
+
+impl Format {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Csv => "csv",
+            Format::JsonLines => "jsonl",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            Format::Csv => "text/csv",
+            Format::JsonLines => "application/jsonl",
+        }
+    }
+}
+
+/// Join list values in a single CSV field
+fn join<T: ToString>(values: &[T]) -> String {
+    values.iter().map(|v| v.to_string()).collect::<Vec<_>>().join(";")
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|v| v.to_string()).unwrap_or_default()
+}
+
+fn json_lines<'a, T: Serialize + 'a>(records: impl IntoIterator<Item = &'a T>) -> String {
+    let mut out = String::new();
+    for record in records {
+        // Serializing plain structs to a string never fails.
+        out.push_str(&serde_json::to_string(record).unwrap_or_default());
+        out.push('\n');
+    }
+    out
+}
+
+/// Per round metrics, one record per sample
+pub fn metrics(samples: &[Sample], format: Format) -> String {
+    if format == Format::JsonLines {
+        return json_lines(samples);
+    }
+
+    let mut out = String::from(
+        "round,cluster_count,head_count,in_game_fraction,mean_energy,min_energy,max_energy,\
+         mean_prob_entering,recharge_events,delivered_bundles,energy_fairness,relay_fairness,\
+         depleted_count,cluster_sizes,energy_histogram\n",
+    );
+    for s in samples {
+        // Write to string will never fail.
+        let _ = writeln!(
+            out,
+            "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}",
+            s.round,
+            s.cluster_count,
+            s.head_count,
+            s.in_game_fraction,
+            s.mean_energy,
+            s.min_energy,
+            s.max_energy,
+            s.mean_prob_entering,
+            s.recharge_events,
+            optional(s.delivered_bundles),
+            s.energy_fairness,
+            s.relay_fairness,
+            s.depleted_count,
+            join(&s.cluster_sizes),
+            join(&s.energy_histogram),
+        );
+    }
+    out
+}
+
+/// Per satellite state, one record per satellite
+pub fn satellites(satellites: &[SatelliteSample], format: Format) -> String {
+    if format == Format::JsonLines {
+        return json_lines(satellites);
+    }
+
+    let mut out = String::from(
+        "round,id,orbit_distance,x,y,energy,in_game,prob_entering,cluster_head,relay_count,safe_mode_rounds\n",
+    );
+    for s in satellites {
+        // Write to string will never fail.
+        let _ = writeln!(
+            out,
+            "{},{},{},{},{},{},{},{},{},{},{}",
+            s.round,
+            s.id,
+            s.orbit_distance,
+            s.x,
+            s.y,
+            s.energy,
+            s.in_game,
+            s.prob_entering,
+            optional(s.cluster_head),
+            s.relay_count,
+            s.safe_mode_rounds,
+        );
+    }
+    out
+}
+
+/// Event log, one record per event
+pub fn events<'a>(events: impl IntoIterator<Item = &'a EventRecord>, format: Format) -> String {
+    if format == Format::JsonLines {
+        return json_lines(events);
+    }
+
+    let mut out = String::from("round,event,satellite,cluster,value\n");
+    for r in events {
+        // Write to string will never fail.
+        let _ = writeln!(
+            out,
+            "{},{},{},{},{}",
+            r.round,
+            r.event.kind().label(),
+            r.event.satellite(),
+            r.event.cluster(),
+            optional(r.event.value()),
+        );
+    }
+    out
+}
+
+/// Save `contents` as `file_name`. Inside the desktop application the file is
+/// written to the downloads folder, in a browser it is downloaded.
+pub fn save(file_name: &str, contents: &str, mime_type: &str) {
+    if desktop::in_tauri() {
+        let (file_name, contents, mime_type) = (file_name.to_string(), contents.to_string(), mime_type.to_string());
+        spawn_local(async move {
+            let args = [("fileName", file_name.as_str()), ("contents", contents.as_str())];
+            if desktop::invoke("save_export", &args).await.is_err() {
+                download(&file_name, &contents, &mime_type);
+            }
+        });
+        return;
+    }
+
+    download(file_name, contents, mime_type);
+}
+
+/// Let the browser download `contents` as `file_name`
+fn download(file_name: &str, contents: &str, mime_type: &str) {
+    let blob = Blob::new_with_options(contents, Some(mime_type));
+    let url = ObjectUrl::from(blob);
+
+    let anchor: HtmlAnchorElement = gloo::utils::document()
+        .create_element("a")
+        .expect("Couldn't create download link")
+        .unchecked_into();
+    anchor.set_href(&url);
+    anchor.set_download(file_name);
+    anchor.click();
+
+    // Revoking the URL right away can cancel the download in some browsers
+    Timeout::new(1000, move || drop(url)).forget();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_has_one_line_per_record() {
+        let samples = vec![
+            Sample { round: 1, cluster_sizes: vec![3, 1], ..Default::default() },
+            Sample { round: 2, delivered_bundles: Some(4), ..Default::default() },
+        ];
+
+        let csv = metrics(&samples, Format::Csv);
+        let lines = csv.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0].split(',').count(), lines[1].split(',').count());
+        assert!(lines[1].starts_with("1,") && lines[1].contains(",3;1,"));
+        assert!(lines[2].contains(",4,"));
+    }
+
+    #[test]
+    fn json_lines_round_trip() {
+        let sats = vec![
+            SatelliteSample { id: 0, cluster_head: Some(0), ..Default::default() },
+            SatelliteSample { id: 1, in_game: true, ..Default::default() },
+        ];
+
+        let jsonl = satellites(&sats, Format::JsonLines);
+        let parsed = jsonl
+            .lines()
+            .map(|line| serde_json::from_str::<SatelliteSample>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(parsed, sats);
+    }
+}
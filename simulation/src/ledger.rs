@@ -0,0 +1,131 @@
+//! This module keeps a record of every change to the energy of a satellite
+//! along with its cause, so the energy shown for a satellite can be traced
+//! back round by round.
+
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+/// Reason for a change in the energy of a satellite
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum EnergyCause {
+    /// Energy spent relaying for the neighbors in the game
+    RelayCost,
+    /// Energy gained from a neighbor relaying in the game
+    GameRecharge,
+    /// Energy harvested from the environment, not produced until harvesting is simulated
+    SolarHarvest,
+    /// Energy above the maximum that was discarded
+    ClampAtMax,
+    /// Energy below zero that was never spent
+    ClampAtZero,
+}
+
+impl EnergyCause {
+    pub fn label(&self) -> &'static str {
+        match self {
+            EnergyCause::RelayCost => "relay cost",
+            EnergyCause::GameRecharge => "game recharge",
+            EnergyCause::SolarHarvest => "solar harvest",
+            EnergyCause::ClampAtMax => "clamp at max",
+            EnergyCause::ClampAtZero => "clamp at zero",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct LedgerEntry {
+    /// Game round the change happened in
+    pub round: u64,
+    pub cause: EnergyCause,
+    /// Signed change in energy
+    pub amount: f32,
+    /// Energy after the change
+    pub balance: f32,
+}
+
+/// The most recent energy changes of a satellite, oldest first
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct EnergyLedger {
+    entries: VecDeque<LedgerEntry>,
+    capacity: usize,
+}
+
+impl Default for EnergyLedger {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+impl EnergyLedger {
+    /// Entries kept per satellite before the oldest ones are dropped
+    pub const DEFAULT_CAPACITY: usize = 256;
+
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn record(&mut self, round: u64, cause: EnergyCause, amount: f32, balance: f32) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+
+        self.entries.push_back(LedgerEntry {
+            round,
+            cause,
+            amount,
+            balance,
+        });
+    }
+
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = &LedgerEntry> + ExactSizeIterator {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The entries recorded up to and including game round `round`
+    pub fn until(&self, round: u64) -> Self {
+        Self {
+            entries: self.entries.iter().take_while(|e| e.round <= round).cloned().collect(),
+            capacity: self.capacity,
+        }
+    }
+
+    /// Energy after each recorded change, oldest first
+    pub fn balances(&self) -> impl Iterator<Item = f32> + '_ {
+        self.entries.iter().map(|e| e.balance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_oldest_entries_past_capacity() {
+        let mut ledger = EnergyLedger::new(3);
+        for round in 0..5 {
+            ledger.record(round, EnergyCause::RelayCost, -1.0, 10.0 - round as f32);
+        }
+
+        assert_eq!(ledger.len(), 3);
+        let rounds: Vec<u64> = ledger.entries().map(|e| e.round).collect();
+        assert_eq!(rounds, vec![2, 3, 4]);
+        assert_eq!(ledger.balances().collect::<Vec<_>>(), vec![8.0, 7.0, 6.0]);
+        assert_eq!(ledger.until(3).balances().collect::<Vec<_>>(), vec![8.0, 7.0]);
+    }
+}
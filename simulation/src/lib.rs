@@ -2,8 +2,22 @@
 
 pub mod math;
 pub mod settings;
+pub mod clock;
+pub mod scenario;
 pub mod simulation;
+pub mod engine;
+pub mod scheduler;
+pub mod snapshot;
+pub mod recorder;
 pub mod quadtree;
+pub mod octree;
 pub mod satellite;
 pub mod components;
-pub mod cluster;
\ No newline at end of file
+pub mod cluster;
+pub mod ledger;
+pub mod metrics;
+pub mod presets;
+pub mod share;
+pub mod desktop;
+pub mod events;
+pub mod export;
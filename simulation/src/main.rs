@@ -1,69 +1,391 @@
+use gtr::clock::{DEFAULT_TIME_WARP, MAX_TIME_WARP};
+use gtr::events::{EventFilter, EventLog};
+use gtr::export::{self, Format};
+use gtr::metrics::{Metrics, Report, Sample};
+use gtr::presets::{PresetError, PresetLibrary};
+use gtr::scenario::{Scenario, ScenarioFormat};
 use gtr::settings::Settings;
-use gtr::simulation::Simulation;
-use gtr::components::slider::Slider;
+use gtr::share;
+use gtr::engine::Activity;
+use gtr::simulation::{Control, RunUntil, Simulation};
+use gtr::snapshot::Snapshot;
+use gtr::components::chart::{Histogram, LineChart};
+use gtr::components::{event_log, run_summary};
+use gtr::components::slider::{self, Slider};
 
+use std::rc::Rc;
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use gloo::file::callbacks::{read_as_text, FileReader};
+use gloo::file::File;
+use web_sys::{HtmlInputElement, HtmlSelectElement};
 use yew::html::Scope;
-use yew::{html, Component, Context, Html};
+use yew::{html, Component, Context, Event, Html, InputEvent, TargetCast};
 
 pub enum Msg {
     ChangeSettings(Settings),
     ResetSettings,
     RestartSimulation,
     TogglePause,
+    SetTimeWarp(f32),
+    Control(Control),
+    EditRunUntil(String),
+    RunUntilTime,
+    EditRunRelays(String),
+    RunUntilRelays,
     ToggleQTree,
+    ToggleCharts,
+    ToggleEvents,
+    RecordReport(Report),
+    FilterEvents(EventFilter),
+    ExportMetrics(Format),
+    ExportSatellites(Format),
+    ExportEvents(Format),
+    NewSeed,
+    SaveScenario(ScenarioFormat),
+    LoadScenario(File),
+    ScenarioLoaded(String, Result<String, String>),
+    SnapshotTaken(Box<Snapshot>),
+    LoadSnapshot(File),
+    SnapshotLoaded(String, Result<String, String>),
+    DismissNotice,
+    SelectPreset(String),
+    EditPresetName(String),
+    SavePreset,
+    RenamePreset,
+    DeletePreset,
+    PresetsLoaded(Option<PresetLibrary>),
+    CopyLink,
+    LinkCopied(Result<(), String>),
 }
 
 pub struct App {
-    settings: Settings,
+    scenario: Scenario,
     generation: usize,
     paused: bool,
+    /// Simulated seconds per second of real time, not part of the scenario so it can change mid-run
+    time_warp: f32,
+    /// Latest request to step or run until a condition, with a counter so repeated requests are sent
+    control: Option<(usize, Control)>,
+    /// Simulated UTC time to run until, as typed in the panel
+    run_until: String,
+    /// Relays to run before stopping, as typed in the panel
+    run_relays: String,
     show_qtree: bool,
+    show_charts: bool,
+    show_events: bool,
+    metrics: Metrics,
+    events: EventLog,
+    event_filter: EventFilter,
+    /// Scenario file being read, reading is cancelled when it is dropped
+    scenario_reader: Option<FileReader>,
+    /// Snapshot file being read, reading is cancelled when it is dropped
+    snapshot_reader: Option<FileReader>,
+    /// Shown until dismissed, e.g. when the saved settings had to be migrated
+    notice: Option<String>,
+    presets: PresetLibrary,
+    /// Preset last applied or saved
+    selected_preset: Option<String>,
+    /// Name typed in for saving or renaming a preset
+    preset_name: String,
 }
 impl Component for App {
     type Message = Msg;
     type Properties = ();
 
-    fn create(_ctx: &Context<Self>) -> Self {
+    fn create(ctx: &Context<Self>) -> Self {
+        // A shared link takes precedence over the stored settings for this session only,
+        // they are replaced once the settings are changed
+        let (scenario, notice) = match share::from_location() {
+            Some(Ok(shared)) => {
+                share::clear_location();
+                (shared.scenario, shared.notice.map(|n| n.to_string()))
+            }
+            Some(Err(e)) => {
+                let (settings, _) = Settings::load();
+                (Scenario::new(settings, rand::random()), Some(format!("Couldn't open the shared link, {e}.")))
+            }
+            None => {
+                let (settings, notice) = Settings::load();
+                (Scenario::new(settings, rand::random()), notice.map(|n| n.to_string()))
+            }
+        };
+
+        // The desktop application keeps the presets on disk as well
+        ctx.link().send_future(async { Msg::PresetsLoaded(PresetLibrary::load_from_disk().await) });
+
         Self {
-            settings: Settings::load(),
+            run_until: (scenario.epoch + chrono::Duration::days(1)).format(RUN_UNTIL_FORMAT).to_string(),
+            run_relays: "100".to_string(),
+            scenario,
             generation: 0,
             paused: false,
-            show_qtree: false
+            time_warp: DEFAULT_TIME_WARP,
+            control: None,
+            show_qtree: false,
+            show_charts: true,
+            show_events: false,
+            metrics: Metrics::new(),
+            events: EventLog::default(),
+            event_filter: EventFilter::default(),
+            scenario_reader: None,
+            snapshot_reader: None,
+            notice,
+            presets: PresetLibrary::load(),
+            selected_preset: None,
+            preset_name: String::new(),
         }
     }
 
-    fn update(&mut self, _ctx: &Context<Self>, msg: Msg) -> bool {
+    fn update(&mut self, ctx: &Context<Self>, msg: Msg) -> bool {
         match msg {
             Msg::ChangeSettings(settings) => {
-                self.settings = settings;
-                self.settings.store();
+                self.apply_settings(settings);
                 true
             }
             Msg::ResetSettings => {
-                self.settings = Settings::default();
+                self.scenario.settings = Settings::default();
+                self.scenario.satellites.clear();
                 Settings::remove();
+                self.metrics.clear();
+                self.events.clear();
                 true
             }
             Msg::RestartSimulation => {
                 self.generation = self.generation.wrapping_add(1);
+                self.metrics.clear();
+                self.events.clear();
                 true
             }
             Msg::TogglePause => {
                 self.paused = !self.paused;
                 true
             }
+            Msg::SetTimeWarp(time_warp) => {
+                self.time_warp = time_warp;
+                true
+            }
+            Msg::Control(control) => {
+                // Stay paused afterwards so the state can be inspected
+                self.paused = true;
+                let count = self.control.as_ref().map_or(0, |(count, _)| count.wrapping_add(1));
+                self.control = Some((count, control));
+                true
+            }
+            Msg::EditRunUntil(time) => {
+                self.run_until = time;
+                false
+            }
+            Msg::RunUntilTime => match parse_run_until(&self.run_until) {
+                Some(time) => {
+                    ctx.link().send_message(Msg::Control(Control::RunUntil(RunUntil::Time(time))));
+                    false
+                }
+                None => {
+                    self.notice = Some(format!("\"{}\" is not a UTC time like 2000-01-02T12:00:00", self.run_until));
+                    true
+                }
+            },
+            Msg::EditRunRelays(relays) => {
+                self.run_relays = relays;
+                false
+            }
+            Msg::RunUntilRelays => match self.run_relays.trim().parse() {
+                Ok(relays) => {
+                    ctx.link().send_message(Msg::Control(Control::RunUntil(RunUntil::Relays(relays))));
+                    false
+                }
+                Err(_) => {
+                    self.notice = Some(format!("\"{}\" is not a number of relays", self.run_relays));
+                    true
+                }
+            },
             Msg::ToggleQTree => {
                 self.show_qtree = !self.show_qtree;
                 true
             }
+            Msg::ToggleCharts => {
+                self.show_charts = !self.show_charts;
+                true
+            }
+            Msg::ToggleEvents => {
+                self.show_events = !self.show_events;
+                true
+            }
+            Msg::RecordReport(mut report) => {
+                self.events.extend(std::mem::take(&mut report.events));
+                self.metrics.record_report(report);
+                self.show_charts || self.show_events
+            }
+            Msg::FilterEvents(filter) => {
+                self.event_filter = filter;
+                true
+            }
+            Msg::ExportMetrics(format) => {
+                let contents = export::metrics(self.metrics.samples(), format);
+                export::save(&Self::export_file_name("metrics", format.extension()), &contents, format.mime_type());
+                false
+            }
+            Msg::ExportSatellites(format) => {
+                let contents = export::satellites(self.metrics.satellites(), format);
+                export::save(&Self::export_file_name("satellites", format.extension()), &contents, format.mime_type());
+                false
+            }
+            Msg::ExportEvents(format) => {
+                let contents = export::events(self.events.filtered(&self.event_filter), format);
+                export::save(&Self::export_file_name("events", format.extension()), &contents, format.mime_type());
+                false
+            }
+            Msg::NewSeed => {
+                self.scenario.seed = rand::random();
+                self.metrics.clear();
+                self.events.clear();
+                true
+            }
+            Msg::SaveScenario(format) => {
+                // List the satellites so the file describes the whole experiment
+                let mut scenario = self.scenario.clone();
+                scenario.satellites = scenario.satellite_definitions();
+                let contents = scenario.to_string(format);
+                export::save(&Self::export_file_name("scenario", format.extension()), &contents, format.mime_type());
+                false
+            }
+            Msg::LoadScenario(file) => {
+                let link = ctx.link().clone();
+                let file_name = file.name();
+                self.scenario_reader = Some(read_as_text(&file, move |result| {
+                    link.send_message(Msg::ScenarioLoaded(file_name, result.map_err(|e| e.to_string())));
+                }));
+                false
+            }
+            Msg::SnapshotTaken(snapshot) => {
+                export::save(&Self::export_file_name("snapshot", "json"), &snapshot.to_json(), "application/json");
+                false
+            }
+            Msg::LoadSnapshot(file) => {
+                let link = ctx.link().clone();
+                let file_name = file.name();
+                self.snapshot_reader = Some(read_as_text(&file, move |result| {
+                    link.send_message(Msg::SnapshotLoaded(file_name, result.map_err(|e| e.to_string())));
+                }));
+                false
+            }
+            Msg::DismissNotice => {
+                self.notice = None;
+                true
+            }
+            Msg::SelectPreset(name) => {
+                if let Some(preset) = self.presets.get(&name) {
+                    self.apply_settings(preset.settings.clone());
+                    self.preset_name = name.clone();
+                    self.selected_preset = Some(name);
+                }
+                true
+            }
+            Msg::EditPresetName(name) => {
+                self.preset_name = name;
+                false
+            }
+            Msg::SavePreset => {
+                let settings = self.scenario.settings.clone();
+                let result = self.presets.save(&self.preset_name, settings);
+                self.update_presets(result.map(|_| Some(self.preset_name.trim().to_string())))
+            }
+            Msg::RenamePreset => {
+                let Some(selected) = self.selected_preset.clone() else {
+                    self.notice = Some("Select the preset to rename first".to_string());
+                    return true;
+                };
+                let result = self.presets.rename(&selected, &self.preset_name);
+                self.update_presets(result.map(|_| Some(self.preset_name.trim().to_string())))
+            }
+            Msg::DeletePreset => {
+                let Some(selected) = self.selected_preset.clone() else {
+                    self.notice = Some("Select the preset to delete first".to_string());
+                    return true;
+                };
+                let result = self.presets.delete(&selected);
+                self.update_presets(result.map(|_| None))
+            }
+            Msg::CopyLink => {
+                let link = share::link(&self.scenario);
+                let clipboard = gloo::utils::window().navigator().clipboard();
+                ctx.link().send_future(async move {
+                    let copied = wasm_bindgen_futures::JsFuture::from(clipboard.write_text(&link)).await;
+                    // Show the link when it can't be copied so it can be copied by hand
+                    Msg::LinkCopied(copied.map(|_| ()).map_err(|_| link))
+                });
+                false
+            }
+            Msg::LinkCopied(result) => {
+                self.notice = Some(match result {
+                    Ok(()) => "Link to this run copied to the clipboard.".to_string(),
+                    Err(link) => format!("Couldn't copy the link, copy it from here: {link}"),
+                });
+                true
+            }
+            Msg::PresetsLoaded(presets) => match presets {
+                Some(presets) => {
+                    self.presets = presets;
+                    true
+                }
+                None => false,
+            },
+            Msg::ScenarioLoaded(file_name, result) => {
+                self.scenario_reader = None;
+                let scenario = result.and_then(|text| {
+                    Scenario::parse(&text, ScenarioFormat::from_file_name(&file_name)).map_err(|e| e.to_string())
+                });
+
+                match scenario {
+                    Ok(scenario) => {
+                        self.scenario = scenario;
+                        self.scenario.settings.store();
+                        self.generation = self.generation.wrapping_add(1);
+                        self.metrics.clear();
+                        self.events.clear();
+                        true
+                    }
+                    Err(e) => {
+                        gloo::dialogs::alert(&format!("Couldn't load {file_name}: {e}"));
+                        false
+                    }
+                }
+            }
+            Msg::SnapshotLoaded(file_name, result) => {
+                self.snapshot_reader = None;
+                let snapshot = result.and_then(|text| Snapshot::parse(&text).map_err(|e| e.to_string()));
+
+                match snapshot {
+                    Ok(snapshot) => {
+                        self.notice = Some(format!(
+                            "Restored the snapshot taken at game round {}, {}.",
+                            snapshot.game_round(),
+                            snapshot.time().format("%Y-%m-%d %H:%M:%S UTC")
+                        ));
+                        self.scenario = snapshot.scenario.clone();
+                        self.scenario.settings.store();
+                        self.metrics.clear();
+                        self.events.clear();
+                        ctx.link().send_message(Msg::Control(Control::Restore(Rc::new(snapshot))));
+                        true
+                    }
+                    Err(e) => {
+                        gloo::dialogs::alert(&format!("Couldn't load {file_name}: {e}"));
+                        false
+                    }
+                }
+            }
         }
     }
 
     fn view(&self, ctx: &Context<Self>) -> Html {
         let Self {
-            ref settings,
+            ref scenario,
             generation,
             paused,
+            time_warp,
+            ref control,
             show_qtree,
             
             ..
@@ -71,13 +393,73 @@ impl Component for App {
 
         html! {
             <>
-                <Simulation settings={settings.clone()} {generation} {paused} {show_qtree}/>
+                <Simulation scenario={scenario.clone()} {generation} {paused} {time_warp} control={control.clone()} {show_qtree}
+                    on_report={ctx.link().callback(Msg::RecordReport)}
+                    on_snapshot={ctx.link().callback(|snapshot| Msg::SnapshotTaken(Box::new(snapshot)))}
+                />
                 { self.view_panel(ctx.link()) }
+                if let Some(notice) = &self.notice {
+                    <div class="notice">
+                        <span>{ notice }</span>
+                        <button onclick={ctx.link().callback(|_| Msg::DismissNotice)}>{ "Dismiss" }</button>
+                    </div>
+                }
+                if self.show_charts {
+                    { self.view_charts() }
+                }
+                if self.show_events {
+                    { event_log::render(&self.events, self.event_filter, ctx.link().callback(Msg::FilterEvents)) }
+                }
+                // Summarize the run while it is stopped
+                if paused || self.finished() {
+                    if let Some(summary) = self.metrics.summary() {
+                        { run_summary::render(&summary) }
+                    }
+                }
             </>
         }
     }
 }
 impl App {
+    /// Number of most recent game rounds plotted by the line charts
+    const CHART_WINDOW: usize = 300;
+
+    /// Name of an exported file, stamped with the current time so runs don't overwrite each other
+    fn export_file_name(kind: &str, extension: &str) -> String {
+        let stamp = chrono::Utc::now().format("%Y%m%d-%H%M%S");
+        format!("{kind}-{stamp}.{extension}")
+    }
+
+    /// Use `settings` for the next run, dropping the results of the current one
+    fn apply_settings(&mut self, settings: Settings) {
+        // Listed satellites no longer apply once the count changes
+        if settings.boids != self.scenario.settings.boids {
+            self.scenario.satellites.clear();
+        }
+        self.scenario.settings = settings;
+        self.scenario.settings.store();
+        self.metrics.clear();
+        self.events.clear();
+    }
+
+    /// Store the presets after a change, or show why the change was refused
+    fn update_presets(&mut self, result: Result<Option<String>, PresetError>) -> bool {
+        match result {
+            Ok(selected) => {
+                self.presets.store();
+                self.preset_name = selected.clone().unwrap_or_default();
+                self.selected_preset = selected;
+            }
+            Err(e) => self.notice = Some(e.to_string()),
+        }
+        true
+    }
+
+    /// Whether the run has reached the duration of the scenario
+    fn finished(&self) -> bool {
+        self.metrics.latest().is_some_and(|s| self.scenario.finished(s.round))
+    }
+
     fn view_panel(&self, link: &Scope<Self>) -> Html {
         let pause_text = if self.paused { "Resume" } else { "Pause" };
         html! {
@@ -88,13 +470,141 @@ impl App {
                     <button onclick={link.callback(|_| Msg::ResetSettings)}>{ "Use Defaults" }</button>
                     <button onclick={link.callback(|_| Msg::RestartSimulation)}>{ "Restart" }</button>
                     <button onclick={link.callback(|_| Msg::ToggleQTree)}>{ "Toggle QTree"}</button>
+                    <button onclick={link.callback(|_| Msg::ToggleCharts)}>{ "Toggle Charts"}</button>
+                    <button onclick={link.callback(|_| Msg::ToggleEvents)}>{ "Toggle Events"}</button>
                 </div>
+                <div class="panel__buttons">
+                    <button onclick={link.callback(|_| Msg::ExportMetrics(Format::Csv))}>{ "Metrics CSV" }</button>
+                    <button onclick={link.callback(|_| Msg::ExportMetrics(Format::JsonLines))}>{ "Metrics JSONL" }</button>
+                    <button onclick={link.callback(|_| Msg::ExportSatellites(Format::Csv))}>{ "Satellites CSV" }</button>
+                    <button onclick={link.callback(|_| Msg::ExportSatellites(Format::JsonLines))}>{ "Satellites JSONL" }</button>
+                    <button onclick={link.callback(|_| Msg::ExportEvents(Format::Csv))}>{ "Events CSV" }</button>
+                    <button onclick={link.callback(|_| Msg::ExportEvents(Format::JsonLines))}>{ "Events JSONL" }</button>
+                </div>
+                { self.view_stepping(link) }
+                { self.view_scenario(link) }
+                { self.view_presets(link) }
+            </div>
+        }
+    }
+
+    fn view_presets(&self, link: &Scope<Self>) -> Html {
+        let onselect = link.callback(|e: Event| {
+            let select: HtmlSelectElement = e.target_unchecked_into();
+            Msg::SelectPreset(select.value())
+        });
+        let oninput = link.callback(|e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            Msg::EditPresetName(input.value())
+        });
+        let selected = self.selected_preset.as_deref();
+        let user_selected = selected.is_some_and(|name| !self.presets.is_builtin(name));
+
+        html! {
+            <div class="panel__buttons">
+                <select class="panel__presets" onchange={onselect}>
+                    <option value="" disabled=true selected={selected.is_none()}>{ "Presets" }</option>
+                    { for self.presets.all().map(|(preset, builtin)| {
+                        let label = if builtin { format!("{} (built-in)", preset.name) } else { preset.name.clone() };
+                        html! {
+                            <option value={preset.name.clone()} selected={selected == Some(preset.name.as_str())}>{ label }</option>
+                        }
+                    }) }
+                </select>
+                <input type="text" class="panel__preset-name" placeholder="Preset name"
+                    value={self.preset_name.clone()} {oninput} />
+                <button onclick={link.callback(|_| Msg::SavePreset)}>{ "Save Preset" }</button>
+                <button onclick={link.callback(|_| Msg::RenamePreset)} disabled={!user_selected}>{ "Rename" }</button>
+                <button onclick={link.callback(|_| Msg::DeletePreset)} disabled={!user_selected}>{ "Delete" }</button>
+            </div>
+        }
+    }
+
+    fn view_stepping(&self, link: &Scope<Self>) -> Html {
+        let step = |activity| link.callback(move |_| Msg::Control(Control::Step(activity)));
+        let onload = link.batch_callback(|e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            let file = input.files().and_then(|files| files.get(0));
+            // Clear the input so picking the same file again reloads it
+            input.set_value("");
+            file.map(|file| Msg::LoadSnapshot(File::from(file)))
+        });
+        let oninput = link.callback(|e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            Msg::EditRunUntil(input.value())
+        });
+        let onrelays = link.callback(|e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            Msg::EditRunRelays(input.value())
+        });
+
+        html! {
+            <div class="panel__buttons">
+                <button onclick={step(Activity::Motion)}>{ "Step Motion" }</button>
+                <button onclick={step(Activity::Clustering)}>{ "Step Clustering" }</button>
+                <button onclick={step(Activity::Game)}>{ "Step Game Round" }</button>
+                <input type="datetime-local" step="1" class="panel__run-until" title="Simulated UTC time"
+                    value={self.run_until.clone()} {oninput} />
+                <button onclick={link.callback(|_| Msg::RunUntilTime)}>{ "Run Until Time" }</button>
+                <button onclick={link.callback(|_| Msg::Control(Control::RunUntil(RunUntil::FirstDepletion)))}>
+                    { "Run Until Depletion" }
+                </button>
+                <input type="number" min="1" class="panel__run-until" title="Relays to run, bundles are not simulated yet"
+                    value={self.run_relays.clone()} oninput={onrelays} />
+                <button onclick={link.callback(|_| Msg::RunUntilRelays)}>{ "Run Until Relays" }</button>
+                <button onclick={link.callback(|_| Msg::Control(Control::Snapshot))}>{ "Save Snapshot" }</button>
+                <label class="panel__file">
+                    { "Load Snapshot" }
+                    <input type="file" accept=".json" onchange={onload} />
+                </label>
+            </div>
+        }
+    }
+
+    fn view_scenario(&self, link: &Scope<Self>) -> Html {
+        let onchange = link.batch_callback(|e: Event| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            let file = input.files().and_then(|files| files.get(0));
+            // Clear the input so picking the same file again reloads it
+            input.set_value("");
+            file.map(|file| Msg::LoadScenario(File::from(file)))
+        });
+
+        html! {
+            <div class="panel__buttons">
+                <span class="panel__seed">{ format!("Seed {}", self.scenario.seed) }</span>
+                <button onclick={link.callback(|_| Msg::NewSeed)}>{ "New Seed" }</button>
+                <button onclick={link.callback(|_| Msg::CopyLink)}>{ "Copy Link" }</button>
+                <button onclick={link.callback(|_| Msg::SaveScenario(ScenarioFormat::Toml))}>{ "Save Scenario" }</button>
+                <label class="panel__file">
+                    { "Load Scenario" }
+                    <input type="file" accept=".toml,.json" {onchange} />
+                </label>
             </div>
         }
     }
 
     fn view_settings(&self, link: &Scope<Self>) -> Html {
-        let Self { settings, .. } = self;
+        let settings = &self.scenario.settings;
+
+        // Problems are shown next to their slider, the others are listed below the sections
+        const SLIDER_FIELDS: [&str; 12] = [
+            "boids",
+            "leo_fraction",
+            "geo_fraction",
+            "selfish_fraction",
+            "energy_threshold",
+            "cluster_distance",
+            "comms_cost",
+            "energy_gain",
+            "max_energy",
+            "tick_interval_ms",
+            "game_interval_s",
+            "comms_interval_s",
+        ];
+        let problems = self.scenario.validate();
+        let problems_with = |field| Settings::problems_with(&problems, field);
+        let other_problems = problems.iter().filter(|p| !SLIDER_FIELDS.contains(&p.field));
 
         // This helper macro creates a callback which applies the new value to the current settings
         // and sends `Msg::ChangeSettings`. Thanks to this, we don't need to have
@@ -116,21 +626,164 @@ impl App {
 
         html! {
             <div class="settings">
-                <Slider label="# of Satellites"
-                    min=1.0 max=600.0
-                    onchange={settings_callback!(link, settings; boids as usize)}
-                    value={settings.boids as f32}
+                { Self::view_section("Orbits", html! {
+                    <>
+                        <Slider label="# of Satellites"
+                            min=1.0 max=600.0
+                            onchange={settings_callback!(link, settings; boids as usize)}
+                            value={settings.boids as f32}
+                            problems={problems_with("boids")}
+                        />
+                        <Slider label="Low Earth Orbit Share"
+                            max=1.0 percentage=true
+                            onchange={settings_callback!(link, settings; leo_fraction)}
+                            value={settings.leo_fraction}
+                            problems={problems_with("leo_fraction")}
+                        />
+                        <Slider label="Geostationary Share"
+                            max=1.0 percentage=true
+                            onchange={settings_callback!(link, settings; geo_fraction)}
+                            value={settings.geo_fraction}
+                            problems={problems_with("geo_fraction")}
+                        />
+                    </>
+                }) }
+                { Self::view_section("Clustering", html! {
+                    <>
+                        <Slider label="Cluster Head Energy Threshold"
+                            max={settings.max_energy}
+                            onchange={settings_callback!(link, settings; energy_threshold)}
+                            value={settings.energy_threshold}
+                            problems={problems_with("energy_threshold")}
+                        />
+                        <Slider label="Distance Between Cluster Heads"
+                            min=1.0 max=600.0
+                            onchange={settings_callback!(link, settings; cluster_distance)}
+                            value={settings.cluster_distance}
+                            problems={problems_with("cluster_distance")}
+                        />
+                    </>
+                }) }
+                { Self::view_section("Game", html! {
+                    <>
+                        <Slider label="Comms Cost"
+                            max=20.0 precision=2
+                            onchange={settings_callback!(link, settings; comms_cost)}
+                            value={settings.comms_cost}
+                            problems={problems_with("comms_cost")}
+                        />
+                        <Slider label="Energy Gain"
+                            max=20.0 precision=2
+                            onchange={settings_callback!(link, settings; energy_gain)}
+                            value={settings.energy_gain}
+                            problems={problems_with("energy_gain")}
+                        />
+                        <Slider label="Selfish Share"
+                            max=1.0 percentage=true
+                            onchange={settings_callback!(link, settings; selfish_fraction)}
+                            value={settings.selfish_fraction}
+                            problems={problems_with("selfish_fraction")}
+                        />
+                    </>
+                }) }
+                { Self::view_section("Energy", html! {
+                    <Slider label="Max Energy"
+                        min=1.0 max=1000.0
+                        onchange={settings_callback!(link, settings; max_energy)}
+                        value={settings.max_energy}
+                        problems={problems_with("max_energy")}
+                    />
+                }) }
+                { Self::view_section("Timing", html! {
+                    <>
+                        <Slider label="Tick Interval (ms)"
+                            min=1.0 max=1000.0
+                            onchange={settings_callback!(link, settings; tick_interval_ms as u64)}
+                            value={settings.tick_interval_ms as f32}
+                            problems={problems_with("tick_interval_ms")}
+                        />
+                        <Slider label="Time Warp"
+                            min=1.0 max={MAX_TIME_WARP}
+                            onchange={link.callback(Msg::SetTimeWarp)}
+                            value={self.time_warp}
+                        />
+                        <Slider label="Game Interval (s)"
+                            min=1.0 max=3600.0
+                            onchange={settings_callback!(link, settings; game_interval_s)}
+                            value={settings.game_interval_s}
+                            problems={problems_with("game_interval_s")}
+                        />
+                        <Slider label="Clustering Interval (s)"
+                            min=1.0 max=3600.0
+                            onchange={settings_callback!(link, settings; comms_interval_s)}
+                            value={settings.comms_interval_s}
+                            problems={problems_with("comms_interval_s")}
+                        />
+                    </>
+                }) }
+                <div class="settings__problems">
+                    { other_problems.map(slider::render_problem).collect::<Html>() }
+                </div>
+            </div>
+        }
+    }
+
+    /// Collapsible group of settings, open by default
+    fn view_section(title: &'static str, content: Html) -> Html {
+        html! {
+            <details class="settings__section" open=true>
+                <summary class="settings__title">{ title }</summary>
+                { content }
+            </details>
+        }
+    }
+
+    fn view_charts(&self) -> Html {
+        let samples = self.metrics.samples();
+        let recent = &samples[samples.len().saturating_sub(Self::CHART_WINDOW)..];
+        let series = |value: fn(&Sample) -> f32| {
+            recent.iter().map(|s| (s.round, value(s))).collect::<Vec<_>>()
+        };
+
+        let energy_histogram = self.metrics.latest().map(|s| s.energy_histogram.clone()).unwrap_or_default();
+
+        html! {
+            <div class="charts">
+                <Histogram label="Energy distribution"
+                    bins={energy_histogram}
+                    max={self.scenario.settings.max_energy}
                 />
-                <Slider label="Max Speed"
-                    max=50.0
-                    onchange={settings_callback!(link, settings; max_speed)}
-                    value={settings.max_speed}
+                <LineChart label="Clusters"
+                    series={series(|s| s.cluster_count as f32)}
+                    min={0.0}
+                />
+                <LineChart label="Mean Pe"
+                    series={series(|s| s.mean_prob_entering)}
+                    min={0.0} max={1.0}
+                    percentage=true
+                />
+                <LineChart label="In game"
+                    series={series(|s| s.in_game_fraction)}
+                    min={0.0} max={1.0}
+                    percentage=true
                 />
             </div>
         }
     }
 }
 
+/// Format of the times typed in the run until input, simulated UTC
+const RUN_UNTIL_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// Time typed in the run until input, browsers leave out the seconds when they are zero
+fn parse_run_until(text: &str) -> Option<DateTime<Utc>> {
+    let text = text.trim();
+    NaiveDateTime::parse_from_str(text, RUN_UNTIL_FORMAT)
+        .or_else(|_| NaiveDateTime::parse_from_str(text, "%Y-%m-%dT%H:%M"))
+        .ok()
+        .map(|time| time.and_utc())
+}
+
 fn main() {
     yew::Renderer::<App>::new().render();
 }
@@ -2,6 +2,8 @@ use std::f32::consts::{FRAC_PI_3, PI};
 use std::iter::Sum;
 use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
 
+use serde::{Deserialize, Serialize};
+
 // at the time of writing the TAU constant is still unstable
 pub const TAU: f32 = 2.0 * PI;
 pub const FRAC_TAU_3: f32 = 2.0 * FRAC_PI_3;
@@ -13,7 +15,7 @@ pub fn smallest_angle_between(source: f32, target: f32) -> f32 {
     (d + PI).rem_euclid(TAU) - PI
 }
 
-#[derive(Clone, Copy, Debug, Default, PartialEq)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
 pub struct Vector2D {
     pub x: f32,
     pub y: f32,
@@ -125,6 +127,129 @@ impl Sum for Vector2D {
     }
 }
 
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Vector3D {
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+}
+impl Vector3D {
+    pub const fn new(x: f32, y: f32, z: f32) -> Self {
+        Self { x, y, z }
+    }
+
+    /// Point at `radius` from the origin, `azimuth` measured counter-clockwise from
+    /// the positive x axis and `elevation` from the xy plane towards positive z.
+    pub fn from_spherical(azimuth: f32, elevation: f32, radius: f32) -> Self {
+        let (sin_az, cos_az) = azimuth.sin_cos();
+        let (sin_el, cos_el) = elevation.sin_cos();
+        Self::new(radius * cos_el * cos_az, radius * cos_el * sin_az, radius * sin_el)
+    }
+
+    pub fn magnitude_squared(self) -> f32 {
+        self.x * self.x + self.y * self.y + self.z * self.z
+    }
+
+    pub fn magnitude(self) -> f32 {
+        self.magnitude_squared().sqrt()
+    }
+
+    pub fn dot(self, other: Self) -> f32 {
+        self.x * other.x + self.y * other.y + self.z * other.z
+    }
+
+    pub fn cross(self, other: Self) -> Self {
+        Self::new(
+            self.y * other.z - self.z * other.y,
+            self.z * other.x - self.x * other.z,
+            self.x * other.y - self.y * other.x,
+        )
+    }
+
+    /// Projection on the xy plane
+    pub fn xy(self) -> Vector2D {
+        Vector2D::new(self.x, self.y)
+    }
+}
+
+impl Neg for Vector3D {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Self::new(-self.x, -self.y, -self.z)
+    }
+}
+
+impl AddAssign for Vector3D {
+    fn add_assign(&mut self, other: Self) {
+        self.x += other.x;
+        self.y += other.y;
+        self.z += other.z;
+    }
+}
+impl Add for Vector3D {
+    type Output = Self;
+
+    fn add(mut self, rhs: Self) -> Self::Output {
+        self += rhs;
+        self
+    }
+}
+
+impl SubAssign for Vector3D {
+    fn sub_assign(&mut self, other: Self) {
+        self.x -= other.x;
+        self.y -= other.y;
+        self.z -= other.z;
+    }
+}
+impl Sub for Vector3D {
+    type Output = Self;
+
+    fn sub(mut self, rhs: Self) -> Self::Output {
+        self -= rhs;
+        self
+    }
+}
+
+impl MulAssign<f32> for Vector3D {
+    fn mul_assign(&mut self, scalar: f32) {
+        self.x *= scalar;
+        self.y *= scalar;
+        self.z *= scalar;
+    }
+}
+impl Mul<f32> for Vector3D {
+    type Output = Self;
+
+    fn mul(mut self, rhs: f32) -> Self::Output {
+        self *= rhs;
+        self
+    }
+}
+
+impl DivAssign<f32> for Vector3D {
+    fn div_assign(&mut self, scalar: f32) {
+        self.x /= scalar;
+        self.y /= scalar;
+        self.z /= scalar;
+    }
+}
+impl Div<f32> for Vector3D {
+    type Output = Self;
+
+    fn div(mut self, rhs: f32) -> Self::Output {
+        self /= rhs;
+        self
+    }
+}
+
+impl Sum for Vector3D {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::default(), |sum, v| sum + v)
+    }
+}
+
 pub trait WeightedMean<T = Self>: Sized {
     fn weighted_mean(it: impl Iterator<Item = (T, f32)>) -> Option<Self>;
 }
@@ -0,0 +1,341 @@
+//! This module samples the state of the network after every game round and
+//! keeps the samples as time series, so a run can be inspected and exported
+//! after the fact instead of only through the current frame.
+
+use serde::{Deserialize, Serialize};
+
+use crate::cluster::ClusterMap;
+use crate::events::EventRecord;
+use crate::satellite::{SatelliteEnergy, SatellitePosition, SatelliteProperties};
+
+/// Number of bins of the energy distribution kept in every sample
+pub const ENERGY_BINS: usize = 10;
+
+/// Jain's fairness index of `values`, from `1 / n` when a single value holds
+/// everything to 1 when all values are equal. All zero values are perfectly fair.
+pub fn jain_index(values: impl Iterator<Item = f32>) -> f32 {
+    let (count, sum, sum_squares) = values.fold((0.0, 0.0, 0.0), |(count, sum, sum_squares), value| {
+        (count + 1.0, sum + value, sum_squares + value * value)
+    });
+
+    if sum_squares > 0.0 {
+        sum * sum / (count * sum_squares)
+    } else {
+        1.0
+    }
+}
+
+/// State of the network at the end of a game round
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Sample {
+    /// Game round the sample was taken at, starting at 1
+    pub round: u64,
+    pub cluster_count: usize,
+    /// Size of every cluster, heads included, sorted from largest to smallest
+    pub cluster_sizes: Vec<usize>,
+    pub head_count: usize,
+    /// Fraction of satellites that entered the game this round
+    pub in_game_fraction: f32,
+    pub mean_energy: f32,
+    pub min_energy: f32,
+    pub max_energy: f32,
+    /// Satellites per energy bin, the bins evenly split `0..=max_energy` of the satellites
+    pub energy_histogram: Vec<usize>,
+    /// Mean probability of entering the game over all satellites
+    pub mean_prob_entering: f32,
+    /// Satellites that recharged from a neighbor relaying for them during this round
+    pub recharge_events: usize,
+    /// Bundles delivered during this round, `None` until routing is simulated
+    pub delivered_bundles: Option<usize>,
+    /// Jain's fairness index over the energy of all satellites
+    pub energy_fairness: f32,
+    /// Jain's fairness index over the number of rounds each satellite relayed
+    pub relay_fairness: f32,
+    /// Satellites whose energy has fallen below the comms cost at least once
+    pub depleted_count: usize,
+}
+
+impl Sample {
+    pub fn new(round: u64, energy: &[SatelliteEnergy], clusters: &ClusterMap, recharge_events: usize) -> Self {
+        let mut cluster_sizes = clusters.clusters().iter().map(|c| c.size()).collect::<Vec<_>>();
+        cluster_sizes.sort_unstable_by(|a, b| b.cmp(a));
+
+        let count = energy.len().max(1) as f32;
+        let in_game = energy.iter().filter(|e| e.in_game()).count();
+        let (min_energy, max_energy) = energy.iter().fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), e| {
+            (min.min(e.energy()), max.max(e.energy()))
+        });
+
+        let mut energy_histogram = vec![0; ENERGY_BINS];
+        for e in energy {
+            let bin = (e.energy() / e.max_energy() * ENERGY_BINS as f32) as usize;
+            energy_histogram[bin.min(ENERGY_BINS - 1)] += 1;
+        }
+
+        Self {
+            round,
+            cluster_count: cluster_sizes.len(),
+            head_count: cluster_sizes.len(),
+            cluster_sizes,
+            in_game_fraction: in_game as f32 / count,
+            mean_energy: energy.iter().map(|e| e.energy()).sum::<f32>() / count,
+            min_energy: if energy.is_empty() { 0.0 } else { min_energy },
+            max_energy: if energy.is_empty() { 0.0 } else { max_energy },
+            energy_histogram,
+            mean_prob_entering: energy.iter().map(|e| e.prob_entering()).sum::<f32>() / count,
+            recharge_events,
+            delivered_bundles: None,
+            energy_fairness: jain_index(energy.iter().map(|e| e.energy())),
+            relay_fairness: jain_index(energy.iter().map(|e| e.relay_count() as f32)),
+            depleted_count: energy.iter().filter(|e| e.depleted()).count(),
+        }
+    }
+}
+
+/// State of a single satellite at the end of a game round
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct SatelliteSample {
+    pub round: u64,
+    pub id: usize,
+    /// Altitude of the orbit above the surface in km
+    pub orbit_distance: f32,
+    /// Screen position
+    pub x: f32,
+    pub y: f32,
+    pub energy: f32,
+    pub in_game: bool,
+    pub prob_entering: f32,
+    /// Head of the cluster the satellite belongs to, if any
+    pub cluster_head: Option<usize>,
+    pub relay_count: u32,
+    pub safe_mode_rounds: u32,
+}
+
+impl SatelliteSample {
+    /// Sample every satellite, the three slices are indexed by satellite id
+    pub fn all(
+        round: u64,
+        props: &[SatelliteProperties],
+        positions: &[SatellitePosition],
+        energy: &[SatelliteEnergy],
+        clusters: &ClusterMap,
+    ) -> Vec<Self> {
+        let heads = clusters.membership(props.len());
+
+        props
+            .iter()
+            .zip(positions)
+            .zip(energy)
+            .zip(heads)
+            .map(|(((props, position), energy), cluster_head)| {
+                let screen_position = position.screen_position();
+                Self {
+                    round,
+                    id: props.id(),
+                    orbit_distance: props.distance(),
+                    x: screen_position.x,
+                    y: screen_position.y,
+                    energy: energy.energy(),
+                    in_game: energy.in_game(),
+                    prob_entering: energy.prob_entering(),
+                    cluster_head,
+                    relay_count: energy.relay_count(),
+                    safe_mode_rounds: energy.safe_mode_rounds(),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Everything sampled at the end of a game round
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    pub sample: Sample,
+    pub satellites: Vec<SatelliteSample>,
+    /// Events since the previous report
+    pub events: Vec<EventRecord>,
+}
+
+/// Fairness and lifetime of the network over a whole run
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct Summary {
+    pub rounds: u64,
+    pub energy_fairness: f32,
+    pub relay_fairness: f32,
+    /// Round at which the first satellite was drained
+    pub first_depletion: Option<u64>,
+    /// Round at which half of the satellites had been drained
+    pub half_depleted: Option<u64>,
+    pub mean_safe_mode_rounds: f32,
+    /// `(id, rounds)` of the satellites that spent the most rounds in safe mode, longest first
+    pub most_safe_mode: Vec<(usize, u32)>,
+}
+
+/// Time series of the samples taken during a run
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+pub struct Metrics {
+    samples: Vec<Sample>,
+    /// State of every satellite at the latest sample
+    satellites: Vec<SatelliteSample>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, sample: Sample) {
+        self.samples.push(sample);
+    }
+
+    pub fn record_report(&mut self, report: Report) {
+        self.record(report.sample);
+        self.satellites = report.satellites;
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+        self.satellites.clear();
+    }
+
+    pub fn satellites(&self) -> &[SatelliteSample] {
+        &self.satellites
+    }
+
+    pub fn samples(&self) -> &[Sample] {
+        &self.samples
+    }
+
+    pub fn latest(&self) -> Option<&Sample> {
+        self.samples.last()
+    }
+
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// Time series of a single value, as `(round, value)` pairs
+    pub fn series<F>(&self, value: F) -> Vec<(u64, f32)>
+    where
+        F: Fn(&Sample) -> f32,
+    {
+        self.samples.iter().map(|s| (s.round, value(s))).collect()
+    }
+
+    /// Fairness and lifetime metrics of the run so far, `None` before the first sample
+    pub fn summary(&self) -> Option<Summary> {
+        let latest = self.latest()?;
+        let satellites = self.satellites.len().max(1);
+
+        let first_round_with = |count: usize| self.samples.iter().find(|s| s.depleted_count >= count).map(|s| s.round);
+
+        let mut most_safe_mode = self.satellites.iter().map(|s| (s.id, s.safe_mode_rounds)).collect::<Vec<_>>();
+        most_safe_mode.sort_by_key(|&(_, rounds)| std::cmp::Reverse(rounds));
+        most_safe_mode.truncate(5);
+
+        Some(Summary {
+            rounds: latest.round,
+            energy_fairness: latest.energy_fairness,
+            relay_fairness: latest.relay_fairness,
+            first_depletion: first_round_with(1),
+            half_depleted: first_round_with(satellites.div_ceil(2)),
+            mean_safe_mode_rounds: self.satellites.iter().map(|s| s.safe_mode_rounds as f32).sum::<f32>() / satellites as f32,
+            most_safe_mode,
+        })
+    }
+
+    /// Samples taken between rounds `from` and `to`, both included
+    pub fn between(&self, from: u64, to: u64) -> &[Sample] {
+        let start = self.samples.partition_point(|s| s.round < from);
+        let end = self.samples.partition_point(|s| s.round <= to);
+        &self.samples[start..end.max(start)]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cluster::Cluster;
+    use crate::engine::{Engine, Stop};
+    use crate::scenario::Scenario;
+    use crate::settings::Settings;
+
+    #[test]
+    fn jain_index_bounds() {
+        assert_eq!(jain_index([5.0, 5.0, 5.0, 5.0].into_iter()), 1.0);
+        assert_eq!(jain_index([8.0, 0.0, 0.0, 0.0].into_iter()), 0.25);
+        assert_eq!(jain_index([0.0, 0.0].into_iter()), 1.0);
+        assert!((jain_index([1.0, 3.0].into_iter()) - 0.8).abs() < 1e-6);
+    }
+
+    #[test]
+    fn summary_tracks_depletion() {
+        let mut metrics = Metrics::new();
+        assert!(metrics.summary().is_none());
+
+        for (round, depleted_count) in [(1, 0), (2, 1), (3, 1), (4, 2), (5, 3)] {
+            metrics.record_report(Report {
+                sample: Sample { round, depleted_count, ..Default::default() },
+                satellites: (0..4).map(|id| SatelliteSample { id, safe_mode_rounds: id as u32, ..Default::default() }).collect(),
+                ..Default::default()
+            });
+        }
+
+        let summary = metrics.summary().unwrap();
+        assert_eq!(summary.rounds, 5);
+        assert_eq!(summary.first_depletion, Some(2));
+        assert_eq!(summary.half_depleted, Some(4));
+        assert_eq!(summary.mean_safe_mode_rounds, 1.5);
+        assert_eq!(summary.most_safe_mode[0], (3, 3));
+    }
+
+    #[test]
+    fn sample_summarizes_energy_and_clusters() {
+        let scenario = Scenario::new(Settings { boids: 4, ..Default::default() }, 1);
+        let energy = scenario
+            .satellite_definitions()
+            .iter()
+            .enumerate()
+            .map(|(id, definition)| SatelliteEnergy::new(id, definition, &scenario.settings))
+            .collect::<Vec<_>>();
+
+        let mut clusters = ClusterMap::new();
+        let mut cluster = Cluster::new(0);
+        cluster.add_member(1);
+        cluster.add_member(2);
+        clusters.insert(cluster);
+        clusters.insert(Cluster::new(3));
+
+        let sample = Sample::new(1, &energy, &clusters, 2);
+        assert_eq!(sample.cluster_sizes, vec![3, 1]);
+        assert_eq!(sample.head_count, 2);
+        assert_eq!(sample.in_game_fraction, 0.0);
+        assert_eq!(sample.mean_prob_entering, 1.0);
+        assert!(sample.min_energy <= sample.mean_energy && sample.mean_energy <= sample.max_energy);
+        assert_eq!(sample.energy_histogram.iter().sum::<usize>(), 4);
+
+        let mut metrics = Metrics::new();
+        for round in 1..=5 {
+            metrics.record(Sample { round, ..sample.clone() });
+        }
+        assert_eq!(metrics.between(2, 4).len(), 3);
+        assert_eq!(metrics.series(|s| s.recharge_events as f32)[4], (5, 2.0));
+    }
+
+    #[test]
+    fn charted_fractions_stay_within_bounds() {
+        let mut engine = Engine::new(Scenario::new(Settings { boids: 30, ..Default::default() }, 35));
+        let mut metrics = Metrics::new();
+        engine.run_until_stop(Stop::Time(3600.0), 100_000, |report| metrics.record_report(report));
+        assert!(metrics.len() > 1);
+
+        // The charts of these values are drawn as percentages between 0 and 1
+        for value in [metrics.series(|s| s.mean_prob_entering), metrics.series(|s| s.in_game_fraction)] {
+            assert!(value.iter().all(|&(_, v)| (0.0..=1.0).contains(&v)), "{value:?}");
+        }
+    }
+}
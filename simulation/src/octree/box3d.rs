@@ -0,0 +1,89 @@
+use super::types::Point;
+use crate::quadtree::Number;
+
+/// Axis aligned box spanning from its `min` corner to its `max` corner
+#[derive(Clone, Debug)]
+pub struct Box3d<U>
+where
+    U: Number,
+{
+    pub(crate) min: Point<U>,
+    pub(crate) max: Point<U>,
+}
+
+impl<U> Box3d<U>
+where
+    U: Number,
+{
+    pub fn new(min: Point<U>, max: Point<U>) -> Self {
+        Box3d { min, max }
+    }
+
+    /// Cube of side `2 * half_extent` centered on `center`. With unsigned
+    /// coordinates the center must be at least `half_extent` away from zero.
+    pub fn from_center(center: &Point<U>, half_extent: U) -> Self {
+        Box3d::new(
+            Point::new(center.x - half_extent, center.y - half_extent, center.z - half_extent),
+            Point::new(center.x + half_extent, center.y + half_extent, center.z + half_extent),
+        )
+    }
+
+    pub fn contains(&self, point: &Point<U>) -> bool {
+        (point.x >= self.min.x && point.x <= self.max.x)
+            && (point.y >= self.min.y && point.y <= self.max.y)
+            && (point.z >= self.min.z && point.z <= self.max.z)
+    }
+
+    pub fn intersects_box(&self, other: &Box3d<U>) -> bool {
+        (self.max.x >= other.min.x && self.min.x <= other.max.x)
+            && (self.max.y >= other.min.y && self.min.y <= other.max.y)
+            && (self.max.z >= other.min.z && self.min.z <= other.max.z)
+    }
+
+    /// Squared distance from `point` to the closest point of the box, zero when inside
+    pub fn distance_squared(&self, point: &Point<U>) -> f64 {
+        let axis = |value: U, min: U, max: U| {
+            let value = value.to_f64();
+            (min.to_f64() - value).max(value - max.to_f64()).max(0.0)
+        };
+
+        let dx = axis(point.x, self.min.x, self.max.x);
+        let dy = axis(point.y, self.min.y, self.max.y);
+        let dz = axis(point.z, self.min.z, self.max.z);
+
+        dx * dx + dy * dy + dz * dz
+    }
+
+    fn midpoint(&self) -> Point<U> {
+        let two = U::from(2);
+        Point::new(
+            (self.min.x + self.max.x) / two,
+            (self.min.y + self.max.y) / two,
+            (self.min.z + self.max.z) / two,
+        )
+    }
+
+    /// Index of the octant holding `point`, in the order returned by `subdivide`.
+    /// Bit 0 is set for the upper x half, bit 1 for y and bit 2 for z. Points on
+    /// the midplanes belong to the upper halves so each point maps to one octant.
+    pub fn octant(&self, point: &Point<U>) -> usize {
+        let mid = self.midpoint();
+
+        usize::from(point.x >= mid.x) | usize::from(point.y >= mid.y) << 1 | usize::from(point.z >= mid.z) << 2
+    }
+
+    /// Subdivide the current box into its 8 octants
+    pub(crate) fn subdivide(&self) -> Vec<Box3d<U>> {
+        let mid = self.midpoint();
+
+        (0..8)
+            .map(|octant| {
+                let (min_x, max_x) = if octant & 1 == 0 { (self.min.x, mid.x) } else { (mid.x, self.max.x) };
+                let (min_y, max_y) = if octant & 2 == 0 { (self.min.y, mid.y) } else { (mid.y, self.max.y) };
+                let (min_z, max_z) = if octant & 4 == 0 { (self.min.z, mid.z) } else { (mid.z, self.max.z) };
+
+                Box3d::new(Point::new(min_x, min_y, min_z), Point::new(max_x, max_y, max_z))
+            })
+            .collect()
+    }
+}
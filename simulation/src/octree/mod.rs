@@ -0,0 +1,8 @@
+//! Three dimensional counterpart of the `quadtree` module, used to index
+//! satellites orbiting in different planes. Coordinates use the same
+//! `Number` trait as the quadtree.
+
+pub mod box3d;
+#[allow(clippy::module_inception)]
+pub mod octree;
+pub mod types;
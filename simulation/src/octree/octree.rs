@@ -0,0 +1,441 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use super::box3d::Box3d;
+use super::types::{Entry, Point};
+use crate::quadtree::Number;
+
+fn distance_squared<U: Number>(a: &Point<U>, b: &Point<U>) -> f64 {
+    let dx = a.x.to_f64() - b.x.to_f64();
+    let dy = a.y.to_f64() - b.y.to_f64();
+    let dz = a.z.to_f64() - b.z.to_f64();
+
+    dx * dx + dy * dy + dz * dz
+}
+
+/// Entry found during a nearest neighbor search, ordered by its squared distance
+struct Candidate<'a, U: Number, V> {
+    distance_squared: f64,
+    entry: Entry<U, &'a V>,
+}
+
+impl<U: Number, V> PartialEq for Candidate<'_, U, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<U: Number, V> Eq for Candidate<'_, U, V> {}
+
+impl<U: Number, V> PartialOrd for Candidate<'_, U, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<U: Number, V> Ord for Candidate<'_, U, V> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance_squared.total_cmp(&other.distance_squared)
+    }
+}
+
+/// Depth at which nodes stop subdividing and keep any extra entries in an overflow bucket
+pub const DEFAULT_MAX_DEPTH: usize = 12;
+
+pub struct Octree<U, V>
+where
+    U: Number,
+{
+    capacity: usize,
+    depth: usize,
+    max_depth: usize,
+    boundary: Box3d<U>,
+    entries: Vec<Entry<U, V>>,
+
+    /// Octants indexed as returned by `Box3d::octant`, empty for leaves
+    children: Vec<Octree<U, V>>,
+}
+
+impl<U, V> Octree<U, V>
+where
+    U: Number,
+{
+    pub fn new(boundary: Box3d<U>, capacity: usize) -> Self {
+        Self::with_max_depth(boundary, capacity, DEFAULT_MAX_DEPTH)
+    }
+
+    /// Create an octree whose nodes never subdivide past `max_depth`. Leaves at
+    /// that depth hold every entry routed to them, even above `capacity`.
+    pub fn with_max_depth(boundary: Box3d<U>, capacity: usize, max_depth: usize) -> Self {
+        Self::new_node(boundary, capacity, 0, max_depth)
+    }
+
+    fn new_node(boundary: Box3d<U>, capacity: usize, depth: usize, max_depth: usize) -> Self {
+        Octree {
+            capacity,
+            depth,
+            max_depth,
+            boundary,
+            entries: Vec::new(),
+            children: Vec::new(),
+        }
+    }
+
+    pub fn insert(&mut self, point: Point<U>, value: V) -> bool {
+        // if point is not in boundary, return false
+        if !self.boundary.contains(&point) {
+            return false;
+        }
+
+        self.insert_routed(point, value);
+        true
+    }
+
+    /// Insert a point known to be inside this node's boundary
+    fn insert_routed(&mut self, point: Point<U>, value: V) {
+        // if there is space in the current octree or it can't be split further, add the point here
+        if self.children.is_empty() && (self.entries.len() < self.capacity || self.depth >= self.max_depth) {
+            self.entries.push(Entry::new(point, value));
+            return;
+        }
+
+        // if there is no space in the current octree, subdivide and insert in the octant holding the point
+        if self.children.is_empty() {
+            self.subdivide();
+        }
+
+        let octant = self.boundary.octant(&point);
+        self.children[octant].insert_routed(point, value);
+    }
+
+    /// Remove the entry holding `value` at `point`, merging children back into
+    /// their parent once they fit in its capacity. Returns false if not found.
+    pub fn remove(&mut self, point: &Point<U>, value: &V) -> bool
+    where
+        V: PartialEq,
+    {
+        if !self.boundary.contains(point) {
+            return false;
+        }
+
+        self.remove_routed(point, value)
+    }
+
+    fn remove_routed(&mut self, point: &Point<U>, value: &V) -> bool
+    where
+        V: PartialEq,
+    {
+        if let Some(index) = self.entries.iter().position(|e| e.point == *point && e.value == *value) {
+            self.entries.swap_remove(index);
+            return true;
+        }
+
+        if self.children.is_empty() {
+            return false;
+        }
+
+        let octant = self.boundary.octant(point);
+        let removed = self.children[octant].remove_routed(point, value);
+        if removed {
+            self.merge();
+        }
+
+        removed
+    }
+
+    /// Move the entry holding `value` from `old_point` to `new_point`. The entry is
+    /// kept in its node when the new point is routed through that node, otherwise
+    /// it is reinserted. Returns false and leaves the tree untouched if the entry
+    /// was not found or the new point is outside of the boundary.
+    pub fn update(&mut self, old_point: &Point<U>, new_point: Point<U>, value: V) -> bool
+    where
+        V: PartialEq,
+    {
+        if !self.boundary.contains(&new_point) {
+            return false;
+        }
+
+        if self.boundary.contains(old_point) && self.move_in_place(old_point, &new_point, &value) {
+            return true;
+        }
+
+        if !self.remove(old_point, &value) {
+            return false;
+        }
+
+        self.insert(new_point, value)
+    }
+
+    /// Move an entry within the nodes shared by the routes of both points
+    fn move_in_place(&mut self, old_point: &Point<U>, new_point: &Point<U>, value: &V) -> bool
+    where
+        V: PartialEq,
+    {
+        if let Some(entry) = self.entries.iter_mut().find(|e| e.point == *old_point && e.value == *value) {
+            entry.point = new_point.clone();
+            return true;
+        }
+
+        let octant = self.boundary.octant(old_point);
+        if self.children.is_empty() || octant != self.boundary.octant(new_point) {
+            return false;
+        }
+
+        self.children[octant].move_in_place(old_point, new_point, value)
+    }
+
+    /// Remove every entry while keeping the allocated nodes for reuse
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        for child in self.children.iter_mut() {
+            child.clear();
+        }
+    }
+
+    /// Number of entries stored in the tree
+    pub fn len(&self) -> usize {
+        self.entries.len() + self.children.iter().map(|child| child.len()).sum::<usize>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty() && self.children.iter().all(|child| child.is_empty())
+    }
+
+    /// Pull the entries of leaf children into this node when they all fit
+    fn merge(&mut self) {
+        if self.children.is_empty() {
+            return;
+        }
+
+        let all_leaves = self.children.iter().all(|child| child.children.is_empty());
+        if !all_leaves || self.len() > self.capacity {
+            return;
+        }
+
+        for child in std::mem::take(&mut self.children) {
+            self.entries.extend(child.entries);
+        }
+    }
+
+    fn subdivide(&mut self) {
+        let (capacity, depth, max_depth) = (self.capacity, self.depth + 1, self.max_depth);
+
+        self.children = self
+            .boundary
+            .subdivide()
+            .into_iter()
+            .map(|boundary| Octree::new_node(boundary, capacity, depth, max_depth))
+            .collect();
+    }
+
+    pub fn query_range(&self, range: Box3d<U>) -> Vec<Entry<U, &V>> {
+        let mut entries = Vec::new();
+        self.collect_range(&range, &mut entries);
+        entries
+    }
+
+    fn collect_range<'a>(&'a self, range: &Box3d<U>, entries: &mut Vec<Entry<U, &'a V>>) {
+        if !self.boundary.intersects_box(range) {
+            return;
+        }
+
+        for entry in &self.entries {
+            if range.contains(&entry.point) {
+                entries.push(Entry::new(entry.point.clone(), &entry.value));
+            }
+        }
+
+        for child in &self.children {
+            child.collect_range(range, entries);
+        }
+    }
+
+    /// Entries within distance `radius` of `point`, paired with their distance
+    pub fn query_radius(&self, point: &Point<U>, radius: U) -> Vec<(Entry<U, &V>, U)> {
+        let mut entries = Vec::new();
+        let radius = radius.to_f64();
+        self.collect_radius(point, radius * radius, &mut entries);
+        entries
+    }
+
+    fn collect_radius<'a>(&'a self, point: &Point<U>, radius_squared: f64, entries: &mut Vec<(Entry<U, &'a V>, U)>) {
+        // prune nodes that lie entirely outside of the sphere
+        if self.boundary.distance_squared(point) > radius_squared {
+            return;
+        }
+
+        for entry in &self.entries {
+            let distance = distance_squared(point, &entry.point);
+            if distance <= radius_squared {
+                entries.push((Entry::new(entry.point.clone(), &entry.value), U::from_f64(distance.sqrt())));
+            }
+        }
+
+        for child in &self.children {
+            child.collect_radius(point, radius_squared, entries);
+        }
+    }
+
+    /// Entry closest to `point`, paired with its distance
+    pub fn nearest(&self, point: &Point<U>) -> Option<(Entry<U, &V>, U)> {
+        self.k_nearest(point, 1).pop()
+    }
+
+    /// Up to `k` entries closest to `point`, paired with their distance and sorted from nearest to farthest
+    pub fn k_nearest(&self, point: &Point<U>, k: usize) -> Vec<(Entry<U, &V>, U)> {
+        let mut best = BinaryHeap::with_capacity(k + 1);
+        if k > 0 {
+            self.collect_nearest(point, k, &mut best);
+        }
+
+        best.into_sorted_vec()
+            .into_iter()
+            .map(|c| (c.entry, U::from_f64(c.distance_squared.sqrt())))
+            .collect()
+    }
+
+    fn collect_nearest<'a>(&'a self, point: &Point<U>, k: usize, best: &mut BinaryHeap<Candidate<'a, U, V>>) {
+        // prune nodes that can't hold anything closer than the current k-th candidate
+        if best.len() == k && self.boundary.distance_squared(point) > best.peek().unwrap().distance_squared {
+            return;
+        }
+
+        for entry in &self.entries {
+            let distance_squared = distance_squared(point, &entry.point);
+            if best.len() < k {
+                best.push(Candidate { distance_squared, entry: Entry::new(entry.point.clone(), &entry.value) });
+            } else if distance_squared < best.peek().unwrap().distance_squared {
+                best.pop();
+                best.push(Candidate { distance_squared, entry: Entry::new(entry.point.clone(), &entry.value) });
+            }
+        }
+
+        // visit the closest octants first so the bound tightens early
+        let mut children = self.children.iter().collect::<Vec<_>>();
+        children.sort_by(|a, b| {
+            a.boundary.distance_squared(point).total_cmp(&b.boundary.distance_squared(point))
+        });
+
+        for child in children {
+            child.collect_nearest(point, k, best);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cube(half_size: f32) -> Box3d<f32> {
+        Box3d::new(
+            Point::new(-half_size, -half_size, -half_size),
+            Point::new(half_size, half_size, half_size),
+        )
+    }
+
+    #[test]
+    fn planes_are_separated_by_height() {
+        let mut octree = Octree::new(cube(100.0), 2);
+
+        // Two rings with the same projection on the xy plane, one inclined out of it
+        for id in 0..36 {
+            let angle = (id as f32 * 10.0).to_radians();
+            let (sin, cos) = angle.sin_cos();
+            octree.insert(Point::new(50.0 * cos, 50.0 * sin, 0.0), id);
+            octree.insert(Point::new(50.0 * cos, 50.0 * sin, 40.0 * sin), id + 100);
+        }
+
+        assert_eq!(octree.len(), 72);
+
+        // Above the xy plane the inclined ring is far from the flat one
+        let (nearest, distance) = octree.nearest(&Point::new(0.0, 50.0, 40.0)).unwrap();
+        assert_eq!(*nearest.value, 109);
+        assert!(distance < 1e-3);
+
+        let neighbors = octree.query_radius(&Point::new(0.0, 50.0, 0.0), 5.0);
+        assert!(neighbors.iter().all(|(entry, _)| *entry.value < 100));
+    }
+
+    #[test]
+    fn k_nearest_matches_brute_force() {
+        let mut octree = Octree::new(cube(10.0), 4);
+        let mut points = Vec::new();
+        for x in -10..=10 {
+            for y in (-10..=10).step_by(5) {
+                for z in (-10..=10).step_by(5) {
+                    let point = Point::new(x as f32, y as f32, z as f32);
+                    assert!(octree.insert(point.clone(), points.len()));
+                    points.push(point);
+                }
+            }
+        }
+
+        let query = Point::new(3.3, -1.2, 4.1);
+        let mut expected = points.iter().map(|p| distance_squared(&query, p).sqrt() as f32).collect::<Vec<_>>();
+        expected.sort_by(|a, b| a.total_cmp(b));
+
+        let found = octree.k_nearest(&query, 8).into_iter().map(|(_, d)| d).collect::<Vec<_>>();
+        assert_eq!(found, expected[..8]);
+
+        let range = Box3d::new(Point::new(0.0, 0.0, 0.0), Point::new(2.0, 5.0, 5.0));
+        assert_eq!(octree.query_range(range).len(), 3 * 2 * 2);
+    }
+
+    #[test]
+    fn update_and_remove() {
+        let mut octree = Octree::with_max_depth(cube(10.0), 1, 4);
+        for id in 0..50 {
+            assert!(octree.insert(Point::new(1.0, 1.0, 1.0), id));
+        }
+
+        assert!(octree.update(&Point::new(1.0, 1.0, 1.0), Point::new(-5.0, 2.0, 9.0), 7));
+        assert_eq!(*octree.nearest(&Point::new(-5.0, 2.0, 8.0)).unwrap().0.value, 7);
+
+        for id in 0..50 {
+            let point = if id == 7 { Point::new(-5.0, 2.0, 9.0) } else { Point::new(1.0, 1.0, 1.0) };
+            assert!(octree.remove(&point, &id));
+        }
+        assert!(octree.is_empty());
+        assert!(octree.children.is_empty());
+    }
+
+    #[test]
+    fn update_out_of_bounds_keeps_the_entry() {
+        let mut octree = Octree::new(cube(10.0), 2);
+        for id in 0..10 {
+            assert!(octree.insert(Point::new(id as f32, 1.0, -1.0), id));
+        }
+
+        assert!(!octree.update(&Point::new(3.0, 1.0, -1.0), Point::new(3.0, 1.0, 11.0), 3));
+        assert_eq!(octree.len(), 10);
+        assert_eq!(*octree.nearest(&Point::new(3.0, 1.0, -1.0)).unwrap().0.value, 3);
+    }
+
+    #[test]
+    fn radius_query_matches_brute_force() {
+        // A grid on the midplanes of the nodes along with points in between
+        let mut octree = Octree::new(cube(10.0), 2);
+        let mut points = Vec::new();
+        for x in (-10..=10).step_by(5) {
+            for y in -10..=10 {
+                for z in (-10..=10).step_by(5) {
+                    let point = Point::new(x as f32, y as f32 * 0.75 + 2.5, z as f32);
+                    if octree.insert(point.clone(), points.len()) {
+                        points.push(point);
+                    }
+                }
+            }
+        }
+
+        for center in [Point::new(0.0, 0.0, 0.0), Point::new(5.0, -5.0, 2.5), Point::new(-10.0, 10.0, -10.0)] {
+            for radius in [0.0, 2.5, 5.0, 7.3, 40.0] {
+                let mut found = octree.query_radius(&center, radius).iter().map(|(e, _)| *e.value).collect::<Vec<_>>();
+                found.sort();
+                let expected = (0..points.len())
+                    .filter(|&id| distance_squared(&center, &points[id]) <= (radius as f64).powi(2))
+                    .collect::<Vec<_>>();
+                assert_eq!(found, expected, "radius {radius} around {center:?}");
+            }
+        }
+    }
+}
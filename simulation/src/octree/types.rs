@@ -0,0 +1,59 @@
+use crate::math::Vector3D;
+use crate::quadtree::Number;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Point<U>
+where
+    U: Number,
+{
+    pub x: U,
+    pub y: U,
+    pub z: U,
+}
+
+impl<U> Point<U> where U: Number {
+    pub fn new(x: U, y: U, z: U) -> Point<U> {
+        Point { x, y, z }
+    }
+}
+
+impl<U> From<Vector3D> for Point<U>
+where
+    U: Number,
+{
+    fn from(vector: Vector3D) -> Self {
+        Point::new(
+            U::from_f64(vector.x as f64),
+            U::from_f64(vector.y as f64),
+            U::from_f64(vector.z as f64),
+        )
+    }
+}
+
+impl<U> From<Point<U>> for Vector3D
+where
+    U: Number,
+{
+    fn from(point: Point<U>) -> Self {
+        Vector3D::new(
+            point.x.to_f64() as f32,
+            point.y.to_f64() as f32,
+            point.z.to_f64() as f32,
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct Entry<U, V>
+where
+    U: Number,
+{
+    pub point: Point<U>,
+    pub value: V,
+}
+
+impl<U, V> Entry<U, V> where U: Number {
+    pub fn new(point: Point<U>, value: V) -> Self {
+        Entry { point, value }
+    }
+}
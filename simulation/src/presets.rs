@@ -0,0 +1,280 @@
+//! This module keeps named settings presets: the built-in standard
+//! configurations and the ones saved by the user. User presets are stored in
+//! LocalStorage next to the settings, and in the desktop application also in
+//! a file in its configuration folder.
+
+use std::fmt;
+
+use gloo::storage::{LocalStorage, Storage};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::desktop;
+use crate::settings::Settings;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Preset {
+    pub name: String,
+    pub settings: Settings,
+}
+
+/// Standard configurations shipped with the simulation
+pub fn builtin() -> Vec<Preset> {
+    let defaults = Settings::default();
+    let preset = |name: &str, settings: Settings| Preset { name: name.to_string(), settings };
+
+    vec![
+        preset("Default", defaults.clone()),
+        preset(
+            "Sparse GEO relay",
+            Settings {
+                boids: 16,
+                geo_fraction: 1.0,
+                cluster_distance: 200.0,
+                ..defaults.clone()
+            },
+        ),
+        preset(
+            "Dense LEO shell",
+            Settings {
+                boids: 400,
+                leo_fraction: 1.0,
+                cluster_distance: 5.0,
+                ..defaults.clone()
+            },
+        ),
+        preset(
+            "Energy-starved",
+            Settings {
+                energy_threshold: 60.0,
+                comms_cost: 4.0,
+                energy_gain: 3.0,
+                ..defaults.clone()
+            },
+        ),
+        preset(
+            "Selfish population",
+            Settings {
+                selfish_fraction: 0.5,
+                ..defaults
+            },
+        ),
+    ]
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PresetError {
+    EmptyName,
+    /// Built-in presets can't be overwritten, renamed or deleted
+    Builtin(String),
+    Missing(String),
+    /// Another preset already has the name
+    Exists(String),
+}
+
+impl fmt::Display for PresetError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PresetError::EmptyName => write!(f, "Presets need a name"),
+            PresetError::Builtin(name) => write!(f, "\"{name}\" is a built-in preset and can't be changed"),
+            PresetError::Missing(name) => write!(f, "There is no preset named \"{name}\""),
+            PresetError::Exists(name) => write!(f, "A preset named \"{name}\" already exists"),
+        }
+    }
+}
+
+/// Presets as they are stored, the settings keep their version so they can be migrated
+#[derive(Deserialize, Serialize)]
+struct StoredPreset {
+    name: String,
+    settings: Value,
+}
+
+/// The built-in presets followed by the user presets
+#[derive(Clone, Debug, PartialEq)]
+pub struct PresetLibrary {
+    builtin: Vec<Preset>,
+    user: Vec<Preset>,
+}
+
+impl Default for PresetLibrary {
+    fn default() -> Self {
+        Self::new(Vec::new())
+    }
+}
+
+impl PresetLibrary {
+    const KEY: &'static str = "yew.boids.presets";
+    /// File holding the user presets in the configuration folder of the desktop application
+    const FILE_NAME: &'static str = "presets.json";
+
+    pub fn new(user: Vec<Preset>) -> Self {
+        Self { builtin: builtin(), user }
+    }
+
+    /// User presets stored in LocalStorage
+    pub fn load() -> Self {
+        LocalStorage::get::<Value>(Self::KEY)
+            .ok()
+            .and_then(|value| Self::from_value(value).ok())
+            .unwrap_or_default()
+    }
+
+    /// User presets stored on disk by the desktop application, `None` outside of it
+    /// or when nothing was stored yet
+    pub async fn load_from_disk() -> Option<Self> {
+        if !desktop::in_tauri() {
+            return None;
+        }
+
+        let contents = desktop::invoke("load_presets", &[("fileName", Self::FILE_NAME)]).await.ok()?;
+        let value = serde_json::from_str(&contents.as_string()?).ok()?;
+        Self::from_value(value).ok()
+    }
+
+    /// Keep the user presets in LocalStorage, and on disk in the desktop application
+    pub fn store(&self) {
+        let value = self.to_value();
+        let _ = LocalStorage::set(Self::KEY, &value);
+
+        if desktop::in_tauri() {
+            let contents = value.to_string();
+            wasm_bindgen_futures::spawn_local(async move {
+                let _ = desktop::invoke("save_presets", &[("fileName", Self::FILE_NAME), ("contents", &contents)]).await;
+            });
+        }
+    }
+
+    /// Read stored user presets, migrating their settings to the current version
+    pub fn from_value(value: Value) -> Result<Self, serde_json::Error> {
+        let stored: Vec<StoredPreset> = serde_json::from_value(value)?;
+        let user = stored
+            .into_iter()
+            .map(|preset| Preset {
+                name: preset.name,
+                settings: Settings::from_stored(preset.settings).0,
+            })
+            .collect();
+        Ok(Self::new(user))
+    }
+
+    pub fn to_value(&self) -> Value {
+        let stored = self
+            .user
+            .iter()
+            .map(|preset| StoredPreset {
+                name: preset.name.clone(),
+                settings: preset.settings.to_stored(),
+            })
+            .collect::<Vec<_>>();
+        // Serializing plain structs never fails.
+        serde_json::to_value(stored).unwrap_or_default()
+    }
+
+    /// Every preset along with whether it is built-in
+    pub fn all(&self) -> impl Iterator<Item = (&Preset, bool)> {
+        self.builtin.iter().map(|p| (p, true)).chain(self.user.iter().map(|p| (p, false)))
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Preset> {
+        self.all().map(|(preset, _)| preset).find(|preset| preset.name == name)
+    }
+
+    pub fn is_builtin(&self, name: &str) -> bool {
+        self.builtin.iter().any(|preset| preset.name == name)
+    }
+
+    /// Save `settings` as the user preset `name`, replacing a user preset with the same name
+    pub fn save(&mut self, name: &str, settings: Settings) -> Result<(), PresetError> {
+        let name = Self::check_name(name)?;
+        if self.is_builtin(name) {
+            return Err(PresetError::Builtin(name.to_string()));
+        }
+
+        match self.user.iter_mut().find(|preset| preset.name == name) {
+            Some(preset) => preset.settings = settings,
+            None => self.user.push(Preset { name: name.to_string(), settings }),
+        }
+        Ok(())
+    }
+
+    pub fn rename(&mut self, name: &str, new_name: &str) -> Result<(), PresetError> {
+        let new_name = Self::check_name(new_name)?;
+        if self.is_builtin(name) {
+            return Err(PresetError::Builtin(name.to_string()));
+        }
+        if name != new_name && self.get(new_name).is_some() {
+            return Err(PresetError::Exists(new_name.to_string()));
+        }
+
+        let preset = self
+            .user
+            .iter_mut()
+            .find(|preset| preset.name == name)
+            .ok_or_else(|| PresetError::Missing(name.to_string()))?;
+        preset.name = new_name.to_string();
+        Ok(())
+    }
+
+    pub fn delete(&mut self, name: &str) -> Result<(), PresetError> {
+        if self.is_builtin(name) {
+            return Err(PresetError::Builtin(name.to_string()));
+        }
+
+        let index = self
+            .user
+            .iter()
+            .position(|preset| preset.name == name)
+            .ok_or_else(|| PresetError::Missing(name.to_string()))?;
+        self.user.remove(index);
+        Ok(())
+    }
+
+    fn check_name(name: &str) -> Result<&str, PresetError> {
+        let name = name.trim();
+        if name.is_empty() {
+            Err(PresetError::EmptyName)
+        } else {
+            Ok(name)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builtin_presets_are_valid() {
+        for preset in builtin() {
+            assert!(!preset.settings.has_errors(), "{} has errors", preset.name);
+        }
+    }
+
+    #[test]
+    fn saves_renames_and_deletes_user_presets() {
+        let mut library = PresetLibrary::default();
+        let settings = Settings { boids: 7, ..Default::default() };
+
+        library.save(" Seven ", settings.clone()).unwrap();
+        assert_eq!(library.get("Seven").unwrap().settings, settings);
+        assert_eq!(library.save("Default", settings.clone()), Err(PresetError::Builtin("Default".to_string())));
+        assert_eq!(library.save("  ", settings.clone()), Err(PresetError::EmptyName));
+
+        assert_eq!(library.rename("Seven", "Default"), Err(PresetError::Exists("Default".to_string())));
+        library.rename("Seven", "Small").unwrap();
+        assert!(library.get("Seven").is_none());
+
+        assert_eq!(library.delete("Energy-starved"), Err(PresetError::Builtin("Energy-starved".to_string())));
+        library.delete("Small").unwrap();
+        assert_eq!(library, PresetLibrary::default());
+    }
+
+    #[test]
+    fn round_trips_user_presets() {
+        let mut library = PresetLibrary::default();
+        library.save("Big", Settings { boids: 300, ..Default::default() }).unwrap();
+
+        assert_eq!(PresetLibrary::from_value(library.to_value()).unwrap(), library);
+    }
+}
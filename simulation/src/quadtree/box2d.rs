@@ -1,27 +1,49 @@
-use super::{types::Point};
+use super::{types::Point, Number};
 
 use yew::{html, Html};
 
 
 
 
+/// Quadrant of a box, in the order returned by `Box2d::subdivide`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Quadrant {
+    NorthWest,
+    NorthEast,
+    SouthEast,
+    SouthWest,
+}
+
 #[derive(Clone, Debug)]
-pub struct Box2d
+pub struct Box2d<U>
+where
+    U: Number,
 {
-    pub(crate) top_left: Point<f32>,
-    pub(crate) btm_right: Point<f32>,
+    pub(crate) top_left: Point<U>,
+    pub(crate) btm_right: Point<U>,
 }
 
-impl Box2d
+impl<U> Box2d<U>
+where
+    U: Number,
 {
-    pub fn new(top_left: Point<f32>, btm_right: Point<f32>) -> Self {
+    pub fn new(top_left: Point<U>, btm_right: Point<U>) -> Self {
         Box2d {
             top_left,
             btm_right,
         }
     }
 
-    pub fn contains(&self, point: &Point<f32>) -> bool {
+    /// Square box of side `2 * half_extent` centered on `center`. With unsigned
+    /// coordinates the center must be at least `half_extent` away from zero.
+    pub fn from_center(center: &Point<U>, half_extent: U) -> Self {
+        Box2d::new(
+            Point::new(center.x - half_extent, center.y + half_extent),
+            Point::new(center.x + half_extent, center.y - half_extent),
+        )
+    }
+
+    pub fn contains(&self, point: &Point<U>) -> bool {
         let top_left = &self.top_left;
         let btm_right = &self.btm_right;
 
@@ -29,7 +51,7 @@ impl Box2d
             && (point.y >= btm_right.y && point.y <= top_left.y)
     }
 
-    pub fn intersects_box(&self, other: &Box2d) -> bool {
+    pub fn intersects_box(&self, other: &Box2d<U>) -> bool {
         let top_left = &self.top_left;
         let btm_right = &self.btm_right;
 
@@ -39,12 +61,41 @@ impl Box2d
             && (top_left.y >= other.btm_right.y)
     }
 
+    /// Squared distance from `point` to the closest point of the box, zero when inside
+    pub fn distance_squared(&self, point: &Point<U>) -> f64 {
+        let (x, y) = (point.x.to_f64(), point.y.to_f64());
+        let dx = (self.top_left.x.to_f64() - x).max(x - self.btm_right.x.to_f64()).max(0.0);
+        let dy = (self.btm_right.y.to_f64() - y).max(y - self.top_left.y.to_f64()).max(0.0);
+
+        dx * dx + dy * dy
+    }
+
+    fn midpoint(&self) -> (U, U) {
+        let two = U::from(2);
+        (
+            (self.btm_right.x + self.top_left.x) / two,
+            (self.btm_right.y + self.top_left.y) / two,
+        )
+    }
+
+    /// Quadrant holding `point`. Points on the midlines belong to the
+    /// north and east quadrants so each point maps to exactly one of them.
+    pub fn quadrant(&self, point: &Point<U>) -> Quadrant {
+        let (x_midpoint, y_midpoint) = self.midpoint();
+
+        match (point.y >= y_midpoint, point.x >= x_midpoint) {
+            (true, false) => Quadrant::NorthWest,
+            (true, true) => Quadrant::NorthEast,
+            (false, true) => Quadrant::SouthEast,
+            (false, false) => Quadrant::SouthWest,
+        }
+    }
+
     /// Subdivide the current box into 4 new boxes north-west, north-east, south-east, south-west
-    pub(crate) fn subdivide(&self) -> Vec<Box2d> {
+    pub(crate) fn subdivide(&self) -> Vec<Box2d<U>> {
         let top_left = &self.top_left;
         let btm_right = &self.btm_right;
-        let x_midpoint = (btm_right.x + top_left.x) / 2.0;
-        let y_midpoint = (btm_right.y + top_left.y) / 2.0;
+        let (x_midpoint, y_midpoint) = self.midpoint();
         vec![
             // North-west
             Box2d::new(top_left.clone(), Point::new(x_midpoint, y_midpoint)),
@@ -64,11 +115,15 @@ impl Box2d
     }
 
     pub fn render(&self) -> Html {
-        let height = (self.top_left.y - self.btm_right.y).abs().to_string();
-        let width = (self.btm_right.x - self.top_left.x).abs().to_string();
-        let x = self.top_left.x.to_string();
-        let y = self.btm_right.y.to_string();
+        self.render_with_stroke("gray")
+    }
+
+    pub fn render_with_stroke(&self, stroke: &str) -> Html {
+        let height = (self.top_left.y - self.btm_right.y).to_f64().abs().to_string();
+        let width = (self.btm_right.x - self.top_left.x).to_f64().abs().to_string();
+        let x = self.top_left.x.to_f64().to_string();
+        let y = self.btm_right.y.to_f64().to_string();
 
-        html! { <rect x={x} y={y} width={width} height={height} fill="none" stroke="gray" stroke-width="1" /> }
+        html! { <rect x={x} y={y} width={width} height={height} fill="none" stroke={stroke.to_string()} stroke-width="1" /> }
     }
 }
@@ -1,33 +1,32 @@
 use std::fmt::Debug;
 
 pub mod box2d;
+#[allow(clippy::module_inception)]
 pub mod quadtree;
 pub(crate) mod types;
 
-pub trait Number: std::ops::Sub<Output = Self> + std::ops::Add<Output = Self> + std::ops::Div<Output = Self> + std::cmp::PartialOrd + Copy + std::convert::From<u8> + Debug {}
-
-impl Number for f32 {}
-
-impl Number for f64 {}
-
-impl Number for i16 {}
-
-impl Number for i32 {}
-
-impl Number for i64 {}
-
-impl Number for i128 {}
-
-impl Number for isize {}
-
-impl Number for u8 {}
-
-impl Number for u16 {}
-
-impl Number for u32 {}
-
-impl Number for u64 {}
-
-impl Number for u128 {}
-
-impl Number for usize {}
\ No newline at end of file
+pub trait Number: std::ops::Sub<Output = Self> + std::ops::Add<Output = Self> + std::ops::Mul<Output = Self> + std::ops::Div<Output = Self> + std::cmp::PartialOrd + Copy + std::convert::From<u8> + Debug {
+    /// Lossy conversion used for distance computations and rendering
+    fn to_f64(self) -> f64;
+
+    /// Lossy conversion back from a computed distance, integers are truncated
+    fn from_f64(value: f64) -> Self;
+}
+
+macro_rules! impl_number {
+    ($($ty:ty),*) => {
+        $(
+            impl Number for $ty {
+                fn to_f64(self) -> f64 {
+                    self as f64
+                }
+
+                fn from_f64(value: f64) -> Self {
+                    value as $ty
+                }
+            }
+        )*
+    };
+}
+
+impl_number!(f32, f64, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
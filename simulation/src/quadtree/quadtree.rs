@@ -1,26 +1,82 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
 use yew::{Html, html};
-use super::{box2d::Box2d};
+use super::box2d::{Box2d, Quadrant};
 use super::types::{Entry, Point};
+use super::Number;
+
+fn distance_squared<U: Number>(a: &Point<U>, b: &Point<U>) -> f64 {
+    let dx = a.x.to_f64() - b.x.to_f64();
+    let dy = a.y.to_f64() - b.y.to_f64();
+
+    dx * dx + dy * dy
+}
+
+/// Entry found during a nearest neighbor search, ordered by its squared distance
+struct Candidate<'a, U: Number, V> {
+    distance_squared: f64,
+    entry: Entry<U, &'a V>,
+}
+
+impl<U: Number, V> PartialEq for Candidate<'_, U, V> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<U: Number, V> Eq for Candidate<'_, U, V> {}
+
+impl<U: Number, V> PartialOrd for Candidate<'_, U, V> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<U: Number, V> Ord for Candidate<'_, U, V> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance_squared.total_cmp(&other.distance_squared)
+    }
+}
 
-pub struct QuadTree<V>
+/// Depth at which nodes stop subdividing and keep any extra entries in an overflow bucket
+pub const DEFAULT_MAX_DEPTH: usize = 16;
+
+pub struct QuadTree<U, V>
+where
+    U: Number,
 {
     capacity: usize,
-    boundary: Box2d,
-    entries: Vec<Entry<f32, V>>,
+    depth: usize,
+    max_depth: usize,
+    boundary: Box2d<U>,
+    entries: Vec<Entry<U, V>>,
 
-    north_west: Option<Box<QuadTree<V>>>,
-    north_east: Option<Box<QuadTree<V>>>,
-    south_west: Option<Box<QuadTree<V>>>,
-    south_east: Option<Box<QuadTree<V>>>,
+    north_west: Option<Box<QuadTree<U, V>>>,
+    north_east: Option<Box<QuadTree<U, V>>>,
+    south_west: Option<Box<QuadTree<U, V>>>,
+    south_east: Option<Box<QuadTree<U, V>>>,
 }
 
-impl<V> QuadTree<V>
+impl<U, V> QuadTree<U, V>
 where
-    V: Clone,
+    U: Number,
 {
-    pub fn new(boundary: Box2d, capacity: usize) -> Self {
+    pub fn new(boundary: Box2d<U>, capacity: usize) -> Self {
+        Self::with_max_depth(boundary, capacity, DEFAULT_MAX_DEPTH)
+    }
+
+    /// Create a quadtree whose nodes never subdivide past `max_depth`. Leaves at
+    /// that depth hold every entry routed to them, even above `capacity`.
+    pub fn with_max_depth(boundary: Box2d<U>, capacity: usize, max_depth: usize) -> Self {
+        Self::new_node(boundary, capacity, 0, max_depth)
+    }
+
+    fn new_node(boundary: Box2d<U>, capacity: usize, depth: usize, max_depth: usize) -> Self {
         QuadTree {
             capacity,
+            depth,
+            max_depth,
             boundary,
             entries: Vec::new(),
             north_west: None,
@@ -30,74 +86,309 @@ where
         }
     }
 
-    pub fn insert(&mut self, point: Point<f32>, value: V) -> bool {
+    pub fn insert(&mut self, point: Point<U>, value: V) -> bool {
         // if point is not in boundary, return false
         if !self.boundary.contains(&point) {
             return false;
         }
 
-        // if there is space in the current quadtree, add the point and return true
-        if self.entries.len() < self.capacity && self.north_east.is_none() {
+        self.insert_routed(point, value);
+        true
+    }
+
+    /// Insert a point known to be inside this node's boundary
+    fn insert_routed(&mut self, point: Point<U>, value: V) {
+        // if there is space in the current quadtree or it can't be split further, add the point here
+        if self.north_east.is_none() && (self.entries.len() < self.capacity || self.depth >= self.max_depth) {
             self.entries.push(Entry::new(point, value));
-            return true;
+            return;
         }
 
-        // if there is no space in the current quadtree, subdivide and try to insert the point
+        // if there is no space in the current quadtree, subdivide and insert in the quadrant holding the point
         if self.north_east.is_none() {
             self.subdivide();
         }
 
-        // try to insert the point in the sub quadtree
-        if self.north_west.as_mut().unwrap().insert(point.clone(), value.clone()) {
-            return true;
+        self.child_mut(&point).insert_routed(point, value);
+    }
+
+    /// Remove the entry holding `value` at `point`, merging children back into
+    /// their parent once they fit in its capacity. Returns false if not found.
+    pub fn remove(&mut self, point: &Point<U>, value: &V) -> bool
+    where
+        V: PartialEq,
+    {
+        if !self.boundary.contains(point) {
+            return false;
         }
-        if self.north_east.as_mut().unwrap().insert(point.clone(), value.clone()) {
+
+        self.remove_routed(point, value)
+    }
+
+    fn remove_routed(&mut self, point: &Point<U>, value: &V) -> bool
+    where
+        V: PartialEq,
+    {
+        if let Some(index) = self.entries.iter().position(|e| e.point == *point && e.value == *value) {
+            self.entries.swap_remove(index);
             return true;
         }
-        if self.south_west.as_mut().unwrap().insert(point.clone(), value.clone()) {
+
+        if self.north_east.is_none() {
+            return false;
+        }
+
+        let removed = self.child_mut(point).remove_routed(point, value);
+        if removed {
+            self.merge();
+        }
+
+        removed
+    }
+
+    /// Move the entry holding `value` from `old_point` to `new_point`. The entry is
+    /// kept in its node when the new point is routed through that node, otherwise
+    /// it is reinserted. Returns false and leaves the tree untouched if the entry
+    /// was not found or the new point is outside of the boundary.
+    pub fn update(&mut self, old_point: &Point<U>, new_point: Point<U>, value: V) -> bool
+    where
+        V: PartialEq,
+    {
+        if !self.boundary.contains(&new_point) {
+            return false;
+        }
+
+        if self.boundary.contains(old_point) && self.move_in_place(old_point, &new_point, &value) {
             return true;
         }
-        if self.south_east.as_mut().unwrap().insert(point.clone(), value) {
+
+        if !self.remove(old_point, &value) {
+            return false;
+        }
+
+        self.insert(new_point, value)
+    }
+
+    /// Move an entry within the nodes shared by the routes of both points
+    fn move_in_place(&mut self, old_point: &Point<U>, new_point: &Point<U>, value: &V) -> bool
+    where
+        V: PartialEq,
+    {
+        if let Some(entry) = self.entries.iter_mut().find(|e| e.point == *old_point && e.value == *value) {
+            entry.point = new_point.clone();
             return true;
         }
 
-        // Nothing can be done, return false
-        false
+        if self.north_east.is_none() || self.boundary.quadrant(old_point) != self.boundary.quadrant(new_point) {
+            return false;
+        }
+
+        self.child_mut(old_point).move_in_place(old_point, new_point, value)
+    }
+
+    /// Remove every entry while keeping the allocated nodes for reuse
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        for child in self.children_mut() {
+            child.clear();
+        }
+    }
+
+    /// Number of entries stored in the tree
+    pub fn len(&self) -> usize {
+        self.entries.len() + self.children().map(|child| child.len()).sum::<usize>()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty() && self.children().all(|child| child.is_empty())
+    }
+
+    /// Pull the entries of leaf children into this node when they all fit
+    fn merge(&mut self) {
+        if self.north_east.is_none() {
+            return;
+        }
+
+        let all_leaves = self.children().all(|child| child.north_east.is_none());
+        if !all_leaves || self.len() > self.capacity {
+            return;
+        }
+
+        for child in [&mut self.north_west, &mut self.north_east, &mut self.south_west, &mut self.south_east] {
+            if let Some(child) = child.take() {
+                self.entries.extend(child.entries);
+            }
+        }
     }
 
     fn subdivide(&mut self) {
         let sub_boxes = self.boundary.subdivide();
+        let (capacity, depth, max_depth) = (self.capacity, self.depth + 1, self.max_depth);
+
+        self.north_west = Some(Box::new(QuadTree::new_node(sub_boxes[0].clone(), capacity, depth, max_depth)));
+        self.north_east = Some(Box::new(QuadTree::new_node(sub_boxes[1].clone(), capacity, depth, max_depth)));
+        self.south_east = Some(Box::new(QuadTree::new_node(sub_boxes[2].clone(), capacity, depth, max_depth)));
+        self.south_west = Some(Box::new(QuadTree::new_node(sub_boxes[3].clone(), capacity, depth, max_depth)));
+    }
+
+    /// Child holding `point`, the node must be subdivided
+    fn child_mut(&mut self, point: &Point<U>) -> &mut QuadTree<U, V> {
+        let child = match self.boundary.quadrant(point) {
+            Quadrant::NorthWest => &mut self.north_west,
+            Quadrant::NorthEast => &mut self.north_east,
+            Quadrant::SouthEast => &mut self.south_east,
+            Quadrant::SouthWest => &mut self.south_west,
+        };
+
+        child.as_deref_mut().expect("quadtree node is not subdivided")
+    }
+
+    pub fn query_range(&self, range: Box2d<U>) -> Vec<Entry<U, &V>> {
+        self.range_iter(range)
+            .map(|entry| Entry::new(entry.point.clone(), &entry.value))
+            .collect()
+    }
+
+    /// Iterate over every entry of the tree without collecting them
+    pub fn iter(&self) -> Iter<'_, U, V> {
+        Iter {
+            stack: vec![self],
+            entries: [].iter(),
+        }
+    }
+
+    /// Iterate over the entries inside `range` without collecting them
+    pub fn range_iter(&self, range: Box2d<U>) -> RangeIter<'_, U, V> {
+        let stack = if self.boundary.intersects_box(&range) { vec![self] } else { Vec::new() };
+
+        RangeIter {
+            range,
+            stack,
+            entries: [].iter(),
+        }
+    }
+
+    /// Shape of the tree, computed by walking every node
+    pub fn stats(&self) -> QuadTreeStats {
+        let mut stats = QuadTreeStats::default();
+        self.collect_stats(&mut stats);
+        stats
+    }
+
+    fn collect_stats(&self, stats: &mut QuadTreeStats) {
+        stats.node_count += 1;
+        stats.entry_count += self.entries.len();
+        stats.depth = stats.depth.max(self.depth);
+
+        if self.north_east.is_some() {
+            for child in self.children() {
+                child.collect_stats(stats);
+            }
+            return;
+        }
+
+        stats.leaf_count += 1;
+        if stats.entries_per_leaf.len() <= self.entries.len() {
+            stats.entries_per_leaf.resize(self.entries.len() + 1, 0);
+        }
+        stats.entries_per_leaf[self.entries.len()] += 1;
+    }
 
-        self.north_west = Some(Box::new(QuadTree::new(sub_boxes[0].clone(), self.capacity)));
-        self.north_east = Some(Box::new(QuadTree::new(sub_boxes[1].clone(), self.capacity)));
-        self.south_east = Some(Box::new(QuadTree::new(sub_boxes[2].clone(), self.capacity)));
-        self.south_west = Some(Box::new(QuadTree::new(sub_boxes[3].clone(), self.capacity)));
+    /// Entries within distance `radius` of `point`, paired with their distance
+    pub fn query_radius(&self, point: &Point<U>, radius: U) -> Vec<(Entry<U, &V>, U)> {
+        self.query_radius_traced(point, radius, |_| {})
     }
 
-    pub fn query_range(&self, range: Box2d) -> Vec<Entry<f32, &V>> {
+    /// Same as `query_radius`, calling `visit` with the boundary of every node the
+    /// search descends into, so the traversal can be inspected
+    pub fn query_radius_traced<F>(&self, point: &Point<U>, radius: U, mut visit: F) -> Vec<(Entry<U, &V>, U)>
+    where
+        F: FnMut(&Box2d<U>),
+    {
         let mut entries = Vec::new();
+        let radius = radius.to_f64();
+        self.collect_radius(point, radius * radius, &mut entries, &mut visit);
+        entries
+    }
 
-        if !self.boundary.intersects_box(&range) {
-            return entries;
+    fn collect_radius<'a, F>(&'a self, point: &Point<U>, radius_squared: f64, entries: &mut Vec<(Entry<U, &'a V>, U)>, visit: &mut F)
+    where
+        F: FnMut(&Box2d<U>),
+    {
+        // prune nodes that lie entirely outside of the circle
+        if self.boundary.distance_squared(point) > radius_squared {
+            return;
         }
 
+        visit(&self.boundary);
+
         for entry in &self.entries {
-            if range.contains(&entry.point) {
-                entries.push(Entry::new(entry.point.clone(), &entry.value));
+            let distance = distance_squared(point, &entry.point);
+            if distance <= radius_squared {
+                entries.push((Entry::new(entry.point.clone(), &entry.value), U::from_f64(distance.sqrt())));
             }
         }
 
-        if self.north_east.is_none() {
-            return entries;
+        for child in self.children() {
+            child.collect_radius(point, radius_squared, entries, visit);
+        }
+    }
+
+    /// Entry closest to `point`, paired with its distance
+    pub fn nearest(&self, point: &Point<U>) -> Option<(Entry<U, &V>, U)> {
+        self.k_nearest(point, 1).pop()
+    }
+
+    /// Up to `k` entries closest to `point`, paired with their distance and sorted from nearest to farthest
+    pub fn k_nearest(&self, point: &Point<U>, k: usize) -> Vec<(Entry<U, &V>, U)> {
+        let mut best = BinaryHeap::with_capacity(k + 1);
+        if k > 0 {
+            self.collect_nearest(point, k, &mut best);
         }
 
-        // query the sub quadtree
-        entries.append(&mut self.north_west.as_ref().unwrap().query_range(range.clone()));
-        entries.append(&mut self.north_east.as_ref().unwrap().query_range(range.clone()));
-        entries.append(&mut self.south_west.as_ref().unwrap().query_range(range.clone()));
-        entries.append(&mut self.south_east.as_ref().unwrap().query_range(range));
+        best.into_sorted_vec()
+            .into_iter()
+            .map(|c| (c.entry, U::from_f64(c.distance_squared.sqrt())))
+            .collect()
+    }
 
-        entries
+    fn collect_nearest<'a>(&'a self, point: &Point<U>, k: usize, best: &mut BinaryHeap<Candidate<'a, U, V>>) {
+        // prune nodes that can't hold anything closer than the current k-th candidate
+        if best.len() == k && self.boundary.distance_squared(point) > best.peek().unwrap().distance_squared {
+            return;
+        }
+
+        for entry in &self.entries {
+            let distance_squared = distance_squared(point, &entry.point);
+            if best.len() < k {
+                best.push(Candidate { distance_squared, entry: Entry::new(entry.point.clone(), &entry.value) });
+            } else if distance_squared < best.peek().unwrap().distance_squared {
+                best.pop();
+                best.push(Candidate { distance_squared, entry: Entry::new(entry.point.clone(), &entry.value) });
+            }
+        }
+
+        // visit the closest quadrants first so the bound tightens early
+        let mut children = self.children().collect::<Vec<_>>();
+        children.sort_by(|a, b| {
+            a.boundary.distance_squared(point).total_cmp(&b.boundary.distance_squared(point))
+        });
+
+        for child in children {
+            child.collect_nearest(point, k, best);
+        }
+    }
+
+    fn children(&self) -> impl Iterator<Item = &QuadTree<U, V>> {
+        [&self.north_west, &self.north_east, &self.south_west, &self.south_east]
+            .into_iter()
+            .filter_map(|child| child.as_deref())
+    }
+
+    fn children_mut(&mut self) -> impl Iterator<Item = &mut QuadTree<U, V>> {
+        [&mut self.north_west, &mut self.north_east, &mut self.south_west, &mut self.south_east]
+            .into_iter()
+            .filter_map(|child| child.as_deref_mut())
     }
 
     pub fn render(&self) -> Html {
@@ -116,3 +407,322 @@ where
         }
     }
 }
+
+impl<'a, U, V> IntoIterator for &'a QuadTree<U, V>
+where
+    U: Number,
+{
+    type Item = &'a Entry<U, V>;
+    type IntoIter = Iter<'a, U, V>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// Iterator over all the entries of a quadtree, created by `QuadTree::iter`
+pub struct Iter<'a, U, V>
+where
+    U: Number,
+{
+    stack: Vec<&'a QuadTree<U, V>>,
+    entries: std::slice::Iter<'a, Entry<U, V>>,
+}
+
+impl<'a, U, V> Iterator for Iter<'a, U, V>
+where
+    U: Number,
+{
+    type Item = &'a Entry<U, V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.entries.next() {
+                return Some(entry);
+            }
+
+            let node = self.stack.pop()?;
+            self.entries = node.entries.iter();
+            self.stack.extend(node.children());
+        }
+    }
+}
+
+/// Iterator over the entries of a quadtree inside a range, created by `QuadTree::range_iter`
+pub struct RangeIter<'a, U, V>
+where
+    U: Number,
+{
+    range: Box2d<U>,
+    stack: Vec<&'a QuadTree<U, V>>,
+    entries: std::slice::Iter<'a, Entry<U, V>>,
+}
+
+impl<'a, U, V> Iterator for RangeIter<'a, U, V>
+where
+    U: Number,
+{
+    type Item = &'a Entry<U, V>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(entry) = self.entries.by_ref().find(|e| self.range.contains(&e.point)) {
+                return Some(entry);
+            }
+
+            let node = self.stack.pop()?;
+            self.entries = node.entries.iter();
+
+            // only descend into quadrants overlapping the range
+            let range = &self.range;
+            self.stack.extend(node.children().filter(|child| child.boundary.intersects_box(range)));
+        }
+    }
+}
+
+/// Summary of the shape of a quadtree
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct QuadTreeStats {
+    /// Depth of the deepest node, the root is at depth 0
+    pub depth: usize,
+    pub node_count: usize,
+    pub leaf_count: usize,
+    pub entry_count: usize,
+    /// Number of leaves holding as many entries as the index
+    pub entries_per_leaf: Vec<usize>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(size: f32) -> Box2d<f32> {
+        Box2d::new(Point::new(0.0, size), Point::new(size, 0.0))
+    }
+
+    fn depth<U: Number, V>(tree: &QuadTree<U, V>) -> usize {
+        tree.children().map(|child| depth(child) + 1).max().unwrap_or(0)
+    }
+
+    #[test]
+    fn points_on_shared_edges_are_stored_once() {
+        let mut qtree = QuadTree::new(square(100.0), 1);
+        let points = [
+            (50.0, 50.0),
+            (50.0, 10.0),
+            (50.0, 90.0),
+            (10.0, 50.0),
+            (90.0, 50.0),
+            (0.0, 0.0),
+            (100.0, 100.0),
+            (25.0, 25.0),
+        ];
+
+        for (id, &(x, y)) in points.iter().enumerate() {
+            assert!(qtree.insert(Point::new(x, y), id));
+        }
+
+        assert_eq!(qtree.len(), points.len());
+        assert_eq!(qtree.query_range(square(100.0)).len(), points.len());
+
+        // A range touching the vertical midline finds exactly the points on it
+        let midline = Box2d::new(Point::new(50.0, 100.0), Point::new(50.0, 0.0));
+        assert_eq!(qtree.query_range(midline).len(), 3);
+
+        for (id, &(x, y)) in points.iter().enumerate() {
+            assert!(qtree.remove(&Point::new(x, y), &id));
+        }
+        assert!(qtree.is_empty());
+    }
+
+    #[test]
+    fn points_outside_boundary_are_rejected() {
+        let mut qtree = QuadTree::new(square(100.0), 4);
+
+        assert!(!qtree.insert(Point::new(100.1, 50.0), 0));
+        assert!(!qtree.insert(Point::new(50.0, -0.1), 1));
+        assert!(qtree.is_empty());
+    }
+
+    #[test]
+    fn identical_points_stop_at_max_depth() {
+        let mut qtree = QuadTree::with_max_depth(square(100.0), 4, 6);
+
+        for id in 0..1000 {
+            assert!(qtree.insert(Point::new(33.0, 33.0), id));
+        }
+
+        assert_eq!(depth(&qtree), 6);
+        assert_eq!(qtree.len(), 1000);
+        assert_eq!(qtree.query_radius(&Point::new(33.0, 33.0), 0.0).len(), 1000);
+        assert_eq!(qtree.k_nearest(&Point::new(0.0, 0.0), 10).len(), 10);
+
+        for id in 0..1000 {
+            assert!(qtree.remove(&Point::new(33.0, 33.0), &id));
+        }
+        assert!(qtree.is_empty());
+        assert_eq!(depth(&qtree), 0);
+    }
+
+    #[test]
+    fn update_across_quadrants() {
+        let mut qtree = QuadTree::new(square(100.0), 1);
+        for id in 0..4 {
+            qtree.insert(Point::new(10.0 + id as f32, 10.0), id);
+        }
+
+        assert!(qtree.update(&Point::new(10.0, 10.0), Point::new(50.0, 50.0), 0));
+        assert!(qtree.update(&Point::new(50.0, 50.0), Point::new(90.0, 90.0), 0));
+        assert!(!qtree.update(&Point::new(10.0, 10.0), Point::new(20.0, 20.0), 0));
+
+        let (nearest, distance) = qtree.nearest(&Point::new(100.0, 100.0)).unwrap();
+        assert_eq!(*nearest.value, 0);
+        assert!((distance - 200.0_f32.sqrt()).abs() < 1e-4);
+        assert_eq!(qtree.len(), 4);
+    }
+
+    #[test]
+    fn update_out_of_bounds_keeps_the_entry() {
+        let mut qtree = QuadTree::new(square(100.0), 1);
+        for id in 0..4 {
+            assert!(qtree.insert(Point::new(10.0 + id as f32, 10.0), id));
+        }
+
+        assert!(!qtree.update(&Point::new(12.0, 10.0), Point::new(12.0, 100.1), 2));
+        assert_eq!(qtree.len(), 4);
+        assert_eq!(*qtree.nearest(&Point::new(12.0, 10.0)).unwrap().0.value, 2);
+    }
+
+    #[test]
+    fn integer_grid_coordinates() {
+        let mut qtree = QuadTree::new(Box2d::new(Point::new(0_i32, 64), Point::new(64, 0)), 2);
+        for x in 0..=64 {
+            for y in (0..=64).step_by(8) {
+                assert!(qtree.insert(Point::new(x, y), (x, y)));
+            }
+        }
+
+        let range = Box2d::new(Point::new(10, 16), Point::new(12, 8));
+        assert_eq!(qtree.query_range(range).len(), 6);
+
+        let (nearest, distance) = qtree.nearest(&Point::new(30, 13)).unwrap();
+        assert_eq!(*nearest.value, (30, 16));
+        assert_eq!(distance, 3);
+    }
+
+    #[test]
+    fn high_precision_coordinates() {
+        // GEO satellites a few meters apart, in km from Earth's center
+        let boundary = Box2d::new(Point::new(-50_000.0_f64, 50_000.0), Point::new(50_000.0, -50_000.0));
+        let mut qtree = QuadTree::new(boundary, 4);
+        for id in 0..100 {
+            qtree.insert(Point::new(42_164.0 + id as f64 * 0.001, 0.0), id);
+        }
+
+        let neighbors = qtree.query_radius(&Point::new(42_164.0, 0.0), 0.0105);
+        assert_eq!(neighbors.len(), 11);
+    }
+
+    #[test]
+    fn iterators_and_stats() {
+        let mut qtree = QuadTree::new(square(100.0), 2);
+        for x in 0..10 {
+            for y in 0..10 {
+                qtree.insert(Point::new(x as f32 * 10.0 + 5.0, y as f32 * 10.0 + 5.0), (x, y));
+            }
+        }
+
+        assert_eq!(qtree.iter().count(), 100);
+        assert_eq!((&qtree).into_iter().filter(|e| e.value.0 == 3).count(), 10);
+
+        let range = Box2d::new(Point::new(20.0, 40.0), Point::new(50.0, 20.0));
+        let mut in_range = qtree.range_iter(range.clone()).map(|e| e.value).collect::<Vec<_>>();
+        in_range.sort();
+        assert_eq!(in_range, vec![(2, 2), (2, 3), (3, 2), (3, 3), (4, 2), (4, 3)]);
+        assert_eq!(qtree.query_range(range).len(), 6);
+
+        let stats = qtree.stats();
+        assert_eq!(stats.entry_count, 100);
+        assert_eq!(stats.depth, depth(&qtree));
+        assert_eq!(stats.leaf_count, stats.entries_per_leaf.iter().sum::<usize>());
+        assert_eq!(stats.node_count, stats.leaf_count + (stats.leaf_count - 1) / 3);
+    }
+
+    #[test]
+    fn traced_radius_query_skips_far_nodes() {
+        let mut qtree = QuadTree::new(square(100.0), 1);
+        for x in 0..10 {
+            for y in 0..10 {
+                qtree.insert(Point::new(x as f32 * 10.0 + 5.0, y as f32 * 10.0 + 5.0), (x, y));
+            }
+        }
+
+        let mut visited = Vec::new();
+        let found = qtree.query_radius_traced(&Point::new(5.0, 5.0), 10.0, |b| visited.push(b.clone()));
+
+        assert_eq!(found.len(), 3);
+        assert!(visited.len() < qtree.stats().node_count);
+        assert!(visited.iter().all(|b| b.distance_squared(&Point::new(5.0, 5.0)) <= 100.0));
+    }
+
+    /// Random points mixed with points on a grid, which fall on node boundaries and
+    /// are at equal distances from the query points
+    fn brute_force_points() -> Vec<Point<f32>> {
+        use rand::{Rng, SeedableRng};
+
+        let mut rng = rand_chacha::ChaCha8Rng::seed_from_u64(27);
+        let grid = (0..=8).flat_map(|x| (0..=8).map(move |y| Point::new(x as f32 * 12.5, y as f32 * 12.5)));
+        let random = (0..200).map(|_| Point::new(rng.gen_range(0.0..=100.0), rng.gen_range(0.0..=100.0)));
+        grid.chain(random).collect()
+    }
+
+    fn brute_force_tree(points: &[Point<f32>]) -> QuadTree<f32, usize> {
+        let mut qtree = QuadTree::new(square(100.0), 2);
+        for (id, point) in points.iter().enumerate() {
+            assert!(qtree.insert(point.clone(), id));
+        }
+        qtree
+    }
+
+    #[test]
+    fn radius_query_matches_brute_force() {
+        let points = brute_force_points();
+        let qtree = brute_force_tree(&points);
+
+        for center in [Point::new(50.0, 50.0), Point::new(0.0, 0.0), Point::new(37.5, 62.5), Point::new(71.3, 8.9)] {
+            for radius in [0.0, 12.5, 25.0, 33.3, 150.0] {
+                let mut found = qtree.query_radius(&center, radius).iter().map(|(e, _)| *e.value).collect::<Vec<_>>();
+                found.sort();
+                let expected = (0..points.len())
+                    .filter(|&id| distance_squared(&center, &points[id]) <= (radius as f64).powi(2))
+                    .collect::<Vec<_>>();
+                assert_eq!(found, expected, "radius {radius} around {center:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn k_nearest_matches_brute_force() {
+        let points = brute_force_points();
+        let qtree = brute_force_tree(&points);
+
+        for center in [Point::new(50.0, 50.0), Point::new(12.5, 87.5), Point::new(6.25, 6.25), Point::new(71.3, 8.9)] {
+            let mut all = points.iter().map(|p| distance_squared(&center, p).sqrt() as f32).collect::<Vec<_>>();
+            all.sort_by(f32::total_cmp);
+
+            for k in [0, 1, 4, 9, 30, points.len(), points.len() + 10] {
+                let found = qtree.k_nearest(&center, k);
+                // Ties can be broken either way, so compare the distances and check the entries are distinct
+                let distances = found.iter().map(|(_, d)| *d).collect::<Vec<_>>();
+                assert_eq!(distances, all[..k.min(points.len())], "k {k} around {center:?}");
+                for (entry, distance) in &found {
+                    assert_eq!(*distance, distance_squared(&center, &points[*entry.value]).sqrt() as f32);
+                }
+                let mut ids = found.iter().map(|(e, _)| *e.value).collect::<Vec<_>>();
+                ids.sort();
+                ids.dedup();
+                assert_eq!(ids.len(), found.len());
+            }
+        }
+    }
+}
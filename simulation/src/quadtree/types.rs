@@ -1,6 +1,7 @@
+use crate::math::Vector2D;
 use crate::quadtree::Number;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Point<U>
 where
     U: Number,
@@ -18,6 +19,24 @@ impl<U> Point<U> where U: Number{
     }
 }
 
+impl<U> From<Vector2D> for Point<U>
+where
+    U: Number,
+{
+    fn from(vector: Vector2D) -> Self {
+        Point::new(U::from_f64(vector.x as f64), U::from_f64(vector.y as f64))
+    }
+}
+
+impl<U> From<Point<U>> for Vector2D
+where
+    U: Number,
+{
+    fn from(point: Point<U>) -> Self {
+        Vector2D::new(point.x.to_f64() as f32, point.y.to_f64() as f32)
+    }
+}
+
 #[derive(Debug)]
 pub struct Entry<U, V>
 where
@@ -0,0 +1,173 @@
+//! This module keeps a compact history of the recent ticks of a run: where
+//! every satellite was, its energy, its decision in the game and the cluster
+//! it belonged to. Past ticks can be turned back into satellites and clusters
+//! to be rendered as they were, so a run can be scrubbed and replayed.
+
+use std::collections::VecDeque;
+
+use crate::cluster::{Cluster, ClusterMap};
+use crate::engine::Engine;
+use crate::satellite::{SatelliteEnergy, SatellitePosition, SatelliteProperties};
+
+/// State of one satellite in a recorded tick
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SatelliteFrame {
+    /// Angle along the orbit, the position follows from it
+    pub angle: f32,
+    pub hue: f32,
+    pub energy: f32,
+    pub in_game: bool,
+    pub prob_entering: f32,
+    /// Head of the cluster the satellite belonged to
+    pub cluster: Option<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    /// Simulated seconds since the epoch
+    pub elapsed_secs: f64,
+    pub game_round: u64,
+    /// Indexed by satellite id
+    pub satellites: Vec<SatelliteFrame>,
+}
+
+impl Frame {
+    pub fn capture(engine: &Engine) -> Self {
+        let membership = engine.cluster_map().membership(engine.props().len());
+        let satellites = engine
+            .props()
+            .iter()
+            .zip(engine.positions())
+            .zip(engine.energy())
+            .zip(membership)
+            .map(|(((props, position), energy), cluster)| SatelliteFrame {
+                angle: position.angle(),
+                hue: props.color(),
+                energy: energy.energy(),
+                in_game: energy.in_game(),
+                prob_entering: energy.prob_entering(),
+                cluster,
+            })
+            .collect();
+
+        Self {
+            elapsed_secs: engine.clock().elapsed_secs(),
+            game_round: engine.game_round(),
+            satellites,
+        }
+    }
+
+    /// Set the satellites to their state in this frame and rebuild its clusters. The
+    /// satellites are those of the run the frame was recorded from.
+    pub fn apply(
+        &self,
+        props: &mut [SatelliteProperties],
+        positions: &mut [SatellitePosition],
+        energy: &mut [SatelliteEnergy],
+    ) -> ClusterMap {
+        let mut clusters = ClusterMap::new();
+
+        for (id, frame) in self.satellites.iter().enumerate() {
+            props[id].set_color(frame.hue);
+            positions[id] = SatellitePosition::new(&props[id], frame.angle);
+            energy[id].set_replayed(frame.energy, frame.in_game, frame.prob_entering);
+        }
+
+        // Heads first, then the members in order of their id as in a clustering round
+        for (id, frame) in self.satellites.iter().enumerate() {
+            if frame.cluster == Some(id) {
+                let mut cluster = Cluster::new(id);
+                cluster.set_color(frame.hue);
+                clusters.insert(cluster);
+            }
+        }
+        for (id, frame) in self.satellites.iter().enumerate() {
+            match frame.cluster.and_then(|head| clusters.get_mut(head)) {
+                Some(cluster) if cluster.head() != id => cluster.add_member(id),
+                _ => (),
+            }
+        }
+
+        clusters
+    }
+}
+
+/// The most recent frames of a run, oldest first
+#[derive(Clone, Debug, PartialEq)]
+pub struct Recorder {
+    frames: VecDeque<Frame>,
+    capacity: usize,
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_CAPACITY)
+    }
+}
+
+impl Recorder {
+    /// Frames kept before the oldest ones are dropped, a minute of ticks at the default interval
+    pub const DEFAULT_CAPACITY: usize = 1200;
+
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            frames: VecDeque::with_capacity(capacity.min(Self::DEFAULT_CAPACITY)),
+            capacity: capacity.max(1),
+        }
+    }
+
+    pub fn record(&mut self, frame: Frame) {
+        if self.frames.len() == self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(frame);
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Frame> {
+        self.frames.get(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.frames.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.frames.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::Activity;
+    use crate::scenario::Scenario;
+    use crate::settings::Settings;
+
+    #[test]
+    fn replays_recorded_ticks() {
+        let mut engine = Engine::new(Scenario::new(Settings { boids: 20, ..Default::default() }, 3));
+        let mut recorder = Recorder::new(3);
+        for _ in 0..5 {
+            engine.step_until(Activity::Game, |_| ());
+            recorder.record(Frame::capture(&engine));
+        }
+
+        // Only the latest frames are kept
+        assert_eq!(recorder.len(), 3);
+        assert_eq!(recorder.get(0).unwrap().game_round, 3);
+        let latest = recorder.get(2).unwrap();
+        assert_eq!(latest, &Frame::capture(&engine));
+
+        // Applying the latest frame to the satellites of an earlier frame brings them back
+        let (mut props, mut positions, mut energy) = (engine.props().to_vec(), engine.positions().to_vec(), engine.energy().to_vec());
+        recorder.get(0).unwrap().apply(&mut props, &mut positions, &mut energy);
+        let clusters = latest.apply(&mut props, &mut positions, &mut energy);
+        assert_eq!(positions, engine.positions());
+        assert_eq!(energy, engine.energy());
+        assert_eq!(clusters.membership(props.len()), engine.cluster_map().membership(props.len()));
+    }
+}
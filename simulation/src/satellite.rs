@@ -1,22 +1,29 @@
 // This is a module that encapsulates the state and the logic to render a satellite using the yew framework.
 
 use crate::cluster::Cluster;
+use crate::ledger::{EnergyCause, EnergyLedger};
 use crate::math::{self, Vector2D};
+use crate::scenario::{SatelliteDefinition, Strategy};
 use crate::settings::Settings;
 use crate::simulation::SIZE;
 use rand::prelude::*;
+use serde::{Deserialize, Serialize};
 use yew::{html, Callback, Html};
 
 
-use gloo::console::log;
-use wasm_bindgen::JsValue;
+// Standard gravitational parameter of Earth in km^3/s^2
+const STD_GRAV_PARAM: f32 = 398_600.44;
 
-// Gravitational constant Earth
-const STD_GRAV_PARAM: f32 = 3.986_018_8e11;
+/// Mean radius of Earth in km
+pub const EARTH_RADIUS: f32 = 6371.0;
+
+/// Rotation rate of Earth in rad/s
+pub const EARTH_ROTATION: f32 = 7.292_115e-5;
 
 pub const MAX_DISTANCE: f32 = 40000.0;
 
 
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct SatelliteProperties {
     id: usize,
     angular_velocity: f32,
@@ -25,41 +32,48 @@ pub struct SatelliteProperties {
     hue: f32,
 }
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct SatellitePosition {
     position: Vector2D,
     angle: f32,
 }
 
+/// Effect of a game round on the energy of a satellite
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EnergyChange {
+    /// Spent energy relaying for its neighbors
+    Consumed,
+    /// Recharged from a neighbor relaying for it
+    Recharged,
+    Unchanged,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct SatelliteEnergy {
     id: usize,
     in_game: bool,
+    strategy: Strategy,
     cost: f32,
     gain: f32,
     energy: f32,
     max_energy: f32,
+    /// Probability of entering the latest game, 1 before the first one as a lone satellite always enters
     prob_entering: f32,
+    /// Game rounds in which the satellite relayed for its neighbors
+    relay_count: u32,
+    /// Game rounds sat out because the energy couldn't cover the comms cost
+    safe_mode_rounds: u32,
+    /// Set once relaying has left less energy than the comms cost
+    depleted: bool,
+    ledger: EnergyLedger,
 }
 
 impl SatelliteProperties {
-    pub fn new_random(id: usize) -> Self {
-        let mut rng = rand::thread_rng();
-
-        // choose a random number from 1 to 3 to determine orbit
-        let orbit = rng.gen_range(2..4);
-
-        // use the orbit to generate a random radious following a gaussian distribution
-        let distance = match orbit {
-            1 => rng.gen_range(500..1200) as f32,
-            2 => rng.gen_range(5000..20000) as f32,
-            3 => 36000.0f32,
-            _ => panic!("Invalid orbit value"),
-        };
-
-        // calculate angular velocity using radius
-        let angular_velocity = (STD_GRAV_PARAM / distance.powi(3)).sqrt();
+    pub fn new(id: usize, definition: &SatelliteDefinition, hue: f32) -> Self {
+        let distance = definition.orbit_distance;
 
-        let hue = rng.gen::<f32>() * 360.0;
+        // calculate angular velocity in rad/s using the radius of the orbit
+        let angular_velocity = (STD_GRAV_PARAM / (EARTH_RADIUS + distance).powi(3)).sqrt();
 
         Self {
             id,
@@ -92,12 +106,7 @@ impl SatelliteProperties {
 }
 
 impl SatellitePosition {
-    pub fn new_random(sat: &SatelliteProperties) -> Self {
-        let mut rng = rand::thread_rng();
-
-        // Generate starting angle
-        let angle = rng.gen::<f32>() * math::TAU;
-
+    pub fn new(sat: &SatelliteProperties, angle: f32) -> Self {
         let mut position = Vector2D::from_polar(
             angle,
             (sat.distance / MAX_DISTANCE) * (SIZE.y / 2.0),
@@ -112,9 +121,10 @@ impl SatellitePosition {
         }
     }
 
-    pub fn update(&mut self, sat: &SatelliteProperties, settings: &Settings) {
+    /// Move along the orbit for `dt` simulated seconds
+    pub fn update(&mut self, sat: &SatelliteProperties, dt: f32) {
         // Calculate new position based on angular velocity
-        self.angle += sat.angular_velocity * (settings.tick_interval_ms as f32 / 1000.0);
+        self.angle = (self.angle + sat.angular_velocity * dt) % math::TAU;
         let radius = (sat.distance / MAX_DISTANCE) * (SIZE.y / 2.0);
         self.position = Vector2D::from_polar(self.angle, radius);
 
@@ -127,6 +137,10 @@ impl SatellitePosition {
         self.position
     }
 
+    pub fn angle(&self) -> f32 {
+        self.angle
+    }
+
     pub fn distance_from_earth(&self) -> f32 {
         let x = self.position.x - SIZE.x / 2.0;
         let y = self.position.y - SIZE.y / 2.0;
@@ -136,18 +150,20 @@ impl SatellitePosition {
 }
 
 impl SatelliteEnergy {
-    pub fn new_random(id: usize, settings: &Settings) -> Self {
-        let mut rng = rand::thread_rng();
-        let energy = rng.gen::<f32>() * 100.0;
-
+    pub fn new(id: usize, definition: &SatelliteDefinition, settings: &Settings) -> Self {
         Self {
             id,
             in_game: false,
+            strategy: definition.strategy,
             cost: settings.comms_cost,
             gain: settings.energy_gain,
-            energy,
+            energy: definition.initial_energy,
             max_energy: settings.max_energy,
-            prob_entering: 100.0,
+            prob_entering: 1.0,
+            relay_count: 0,
+            safe_mode_rounds: 0,
+            depleted: false,
+            ledger: EnergyLedger::default(),
         }
     }
 
@@ -155,9 +171,11 @@ impl SatelliteEnergy {
         self.prob_entering
     }
 
-    pub fn update_game(&mut self, cluster: &Cluster) {
+    pub fn update_game(&mut self, cluster: &Cluster, rng: &mut impl Rng) {
         if self.energy < self.cost || self.energy < 0.0 {
+            // Safe mode, not enough energy to relay
             self.in_game = false;
+            self.safe_mode_rounds += 1;
             return;
         }
 
@@ -166,8 +184,21 @@ impl SatelliteEnergy {
             return;
         }
 
+        match self.strategy {
+            Strategy::Nash => (),
+            Strategy::AlwaysRelay => {
+                self.prob_entering = 1.0;
+                self.in_game = true;
+                return;
+            }
+            Strategy::NeverRelay => {
+                self.prob_entering = 0.0;
+                self.in_game = false;
+                return;
+            }
+        }
+
         // Calculate Nash equilibrium probability
-        let mut rng = rand::thread_rng();
         let num_neighbors = (cluster.size() - 1) as f32;
         let prob_entering = 1.0 - (1.0 - ((self.energy - self.cost) / (self.energy + self.gain))).powf(1.0/num_neighbors);
 
@@ -181,53 +212,91 @@ impl SatelliteEnergy {
 
         // Determine if satellite enters game
         self.in_game = rng.gen_bool(prob_entering as f64);
-
-        #[cfg(debug_assertions)]
-        if self.in_game {
-            let debug = format!("id: {} -> entering game: {}", self.id, self.energy);
-            log!(JsValue::from(&debug));
-        } else {
-            let debug = format!("id: {} -> not entering game: {}", self.id, self.energy);
-            log!(JsValue::from(&debug));
-        }
     }
 
-    pub fn update(&mut self, neighbors: Vec<&SatelliteEnergy>) {
+    /// Pay for relaying or collect the payoff of the round, `neighbors_in_game` of the
+    /// other satellites of the cluster entered the game
+    pub fn update(&mut self, round: u64, neighbors_in_game: usize) -> EnergyChange {
         if self.in_game {
             self.energy -= self.cost;
+            self.ledger.record(round, EnergyCause::RelayCost, -self.cost, self.energy);
 
             // Clamp energy to 0
             if self.energy < 0.0 {
+                self.ledger.record(round, EnergyCause::ClampAtZero, -self.energy, 0.0);
                 self.energy = 0.0;
             }
-            let debug = format!("id: {} -> consumed {}", self.id, self.energy);
-            log!(JsValue::from(&debug));
-            return;
-        }
 
-        // Calculate utility based on decisions made by neighbors
-        let neighbors_in_game = neighbors.iter().fold(0, |acc, n| acc + (n.in_game as u32));
+            // Safe mode keeps the leftover energy once it can't cover the next relay
+            if self.energy < self.cost {
+                self.depleted = true;
+            }
+            self.relay_count += 1;
+            return EnergyChange::Consumed;
+        }
 
         // If no neighbors are in the game, get no payoff
         if neighbors_in_game == 0 {
+            EnergyChange::Unchanged
         }
         else {
             // Recharge energy
             self.energy += self.gain;
-            
+            self.ledger.record(round, EnergyCause::GameRecharge, self.gain, self.energy);
+
             // Clamp energy to max energy
             if self.energy > self.max_energy {
+                self.ledger.record(round, EnergyCause::ClampAtMax, self.max_energy - self.energy, self.max_energy);
                 self.energy = self.max_energy;
             }
 
-            let debug = format!("id: {} -> recharge {}", self.id, self.energy);
-            log!(JsValue::from(&debug));
+            EnergyChange::Recharged
         }
     }
 
+    pub fn id(&self) -> usize {
+        self.id
+    }
+
+    pub fn strategy(&self) -> Strategy {
+        self.strategy
+    }
+
     pub fn energy(&self) -> f32 {
         self.energy
     }
+
+    pub fn in_game(&self) -> bool {
+        self.in_game
+    }
+
+    pub fn max_energy(&self) -> f32 {
+        self.max_energy
+    }
+
+    pub fn relay_count(&self) -> u32 {
+        self.relay_count
+    }
+
+    pub fn safe_mode_rounds(&self) -> u32 {
+        self.safe_mode_rounds
+    }
+
+    pub fn ledger(&self) -> &EnergyLedger {
+        &self.ledger
+    }
+
+    /// Whether relaying has ever left the satellite unable to cover the comms cost
+    pub fn depleted(&self) -> bool {
+        self.depleted
+    }
+
+    /// Show the energy and decision recorded for a past tick, only used for replays
+    pub(crate) fn set_replayed(&mut self, energy: f32, in_game: bool, prob_entering: f32) {
+        self.energy = energy;
+        self.in_game = in_game;
+        self.prob_entering = prob_entering;
+    }
 }
 
 pub fn render(sat: &SatelliteProperties, position: &SatellitePosition, game: &SatelliteEnergy, onclick_cb: Callback<usize>) -> Html {
@@ -0,0 +1,451 @@
+//! This module defines the scenario file, which describes a whole experiment:
+//! the settings, the seed of the random number generator, the satellites to
+//! start with, the ground stations and how long to run for. Scenarios are
+//! written as TOML or JSON so they can be shared and checked in.
+
+use std::fmt;
+
+use chrono::{DateTime, TimeZone, Utc};
+use rand::prelude::*;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::math;
+use crate::satellite::MAX_DISTANCE;
+use crate::settings::{Settings, SettingsProblem, Severity};
+
+/// Version of the scenario format written by this build
+pub const SCENARIO_VERSION: u32 = 1;
+
+/// How a satellite decides whether to relay for its cluster
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Strategy {
+    /// Enter the game with the Nash equilibrium probability
+    #[default]
+    Nash,
+    /// Relay whenever there is enough energy
+    AlwaysRelay,
+    /// Never relay, only benefit from the neighbors
+    NeverRelay,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct SatelliteDefinition {
+    /// Altitude of the orbit above the surface in km
+    pub orbit_distance: f32,
+    /// Starting angle along the orbit in radians
+    pub angle: f32,
+    pub initial_energy: f32,
+    #[serde(default)]
+    pub strategy: Strategy,
+}
+
+impl SatelliteDefinition {
+    pub fn random(rng: &mut impl Rng) -> Self {
+        // choose a random number from 1 to 3 to determine orbit
+        let orbit = rng.gen_range(2..4);
+
+        Self {
+            orbit_distance: Self::orbit_distance(orbit, rng),
+            angle: rng.gen::<f32>() * math::TAU,
+            initial_energy: rng.gen::<f32>() * 100.0,
+            strategy: Strategy::Nash,
+        }
+    }
+
+    fn orbit_distance(orbit: u32, rng: &mut impl Rng) -> f32 {
+        // use the orbit to generate a random radious following a gaussian distribution
+        match orbit {
+            1 => rng.gen_range(500..1200) as f32,
+            2 => rng.gen_range(5000..20000) as f32,
+            3 => 36000.0f32,
+            _ => panic!("Invalid orbit value"),
+        }
+    }
+
+    /// Move the satellite to the orbit and strategy picked for it by the shares of
+    /// `settings`. Satellites are left as generated when every share is zero.
+    fn apply_shares(&mut self, rng: &mut impl Rng, settings: &Settings) {
+        let orbit = rng.gen::<f32>();
+        if orbit < settings.leo_fraction {
+            self.orbit_distance = Self::orbit_distance(1, rng);
+        } else if orbit < settings.leo_fraction + settings.geo_fraction {
+            self.orbit_distance = Self::orbit_distance(3, rng);
+        }
+
+        if rng.gen::<f32>() < settings.selfish_fraction {
+            self.strategy = Strategy::NeverRelay;
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct GroundStation {
+    pub name: String,
+    /// Longitude in degrees, east positive
+    pub longitude: f32,
+}
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Scenario {
+    pub version: u32,
+    #[serde(default)]
+    pub name: String,
+    pub seed: u64,
+    /// Game rounds to run for, runs until stopped when not set
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<u64>,
+    /// UTC time the simulated clock starts at
+    #[serde(default = "default_epoch")]
+    pub epoch: DateTime<Utc>,
+    pub settings: Settings,
+    /// Satellites to start with, `settings.boids` satellites are generated from the seed when empty
+    #[serde(default)]
+    pub satellites: Vec<SatelliteDefinition>,
+    #[serde(default)]
+    pub ground_stations: Vec<GroundStation>,
+}
+
+/// The J2000 epoch, 2000-01-01 12:00:00 UTC
+fn default_epoch() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2000, 1, 1, 12, 0, 0).unwrap()
+}
+
+impl Default for Scenario {
+    fn default() -> Self {
+        Self::new(Settings::default(), 0)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ScenarioFormat {
+    Toml,
+    Json,
+}
+
+impl ScenarioFormat {
+    /// Format of a scenario file based on its extension, TOML unless it ends in `.json`
+    pub fn from_file_name(name: &str) -> Self {
+        if name.to_ascii_lowercase().ends_with(".json") {
+            ScenarioFormat::Json
+        } else {
+            ScenarioFormat::Toml
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            ScenarioFormat::Toml => "toml",
+            ScenarioFormat::Json => "json",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            ScenarioFormat::Toml => "application/toml",
+            ScenarioFormat::Json => "application/json",
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ScenarioError {
+    /// The file is not a valid scenario
+    Parse(String),
+    /// The file was written by a newer version of the simulation
+    UnsupportedVersion(u32),
+    /// The scenario can't be run, holds the errors found by `Scenario::validate`
+    Invalid(Vec<SettingsProblem>),
+    Io(String),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScenarioError::Parse(e) => write!(f, "invalid scenario: {e}"),
+            ScenarioError::UnsupportedVersion(v) => write!(
+                f,
+                "scenario version {v} is not supported, this build reads up to version {SCENARIO_VERSION}"
+            ),
+            ScenarioError::Invalid(problems) => {
+                let problems = problems.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+                write!(f, "scenario can't be run: {}", problems.join("; "))
+            }
+            ScenarioError::Io(e) => write!(f, "couldn't access scenario: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl Scenario {
+    pub fn new(settings: Settings, seed: u64) -> Self {
+        Self {
+            version: SCENARIO_VERSION,
+            name: String::new(),
+            seed,
+            duration: None,
+            epoch: default_epoch(),
+            settings,
+            satellites: Vec::new(),
+            ground_stations: Vec::new(),
+        }
+    }
+
+    /// Random number generator for a run of this scenario
+    pub fn rng(&self) -> ChaCha8Rng {
+        ChaCha8Rng::seed_from_u64(self.seed)
+    }
+
+    /// Satellites to start with, generated from the seed unless they are listed
+    pub fn satellite_definitions(&self) -> Vec<SatelliteDefinition> {
+        if !self.satellites.is_empty() {
+            return self.satellites.clone();
+        }
+
+        // Use a stream apart from the one used by the run so listing the
+        // generated satellites doesn't change the rest of the run.
+        let mut rng = self.rng();
+        rng.set_stream(1);
+        // The shares draw from another stream so the same seed generates the same
+        // satellites whatever the shares are set to.
+        let mut shares = self.rng();
+        shares.set_stream(2);
+        (0..self.settings.boids)
+            .map(|_| {
+                let mut satellite = SatelliteDefinition::random(&mut rng);
+                satellite.apply_shares(&mut shares, &self.settings);
+                satellite
+            })
+            .collect()
+    }
+
+    /// Whether the run is over after `round` game rounds
+    pub fn finished(&self, round: u64) -> bool {
+        self.duration.is_some_and(|duration| round >= duration)
+    }
+
+    pub fn parse(text: &str, format: ScenarioFormat) -> Result<Self, ScenarioError> {
+        let mut scenario: Scenario = match format {
+            ScenarioFormat::Toml => toml::from_str(text).map_err(|e| ScenarioError::Parse(e.to_string()))?,
+            ScenarioFormat::Json => serde_json::from_str(text).map_err(|e| ScenarioError::Parse(e.to_string()))?,
+        };
+
+        if scenario.version > SCENARIO_VERSION {
+            return Err(ScenarioError::UnsupportedVersion(scenario.version));
+        }
+
+        // Listed satellites take precedence over the generated count
+        if !scenario.satellites.is_empty() {
+            scenario.settings.boids = scenario.satellites.len();
+        }
+
+        let errors = scenario
+            .validate()
+            .into_iter()
+            .filter(|p| p.severity == Severity::Error)
+            .collect::<Vec<_>>();
+        if !errors.is_empty() {
+            return Err(ScenarioError::Invalid(errors));
+        }
+
+        Ok(scenario)
+    }
+
+    /// Problems with the settings and the listed satellites, see `Settings::validate`
+    pub fn validate(&self) -> Vec<SettingsProblem> {
+        let mut problems = self.settings.validate();
+
+        for (id, satellite) in self.satellites.iter().enumerate() {
+            if !(satellite.orbit_distance.is_finite() && satellite.orbit_distance > 0.0) {
+                problems.push(SettingsProblem {
+                    field: "satellites",
+                    severity: Severity::Error,
+                    message: format!("satellite {id} needs an orbit distance above zero"),
+                });
+            } else if satellite.orbit_distance > MAX_DISTANCE {
+                // Farther satellites would be placed outside of the quadtree
+                problems.push(SettingsProblem {
+                    field: "satellites",
+                    severity: Severity::Error,
+                    message: format!(
+                        "satellite {id} orbits at {}, beyond the max distance ({MAX_DISTANCE})",
+                        satellite.orbit_distance
+                    ),
+                });
+            }
+
+            if !(0.0..=self.settings.max_energy).contains(&satellite.initial_energy) {
+                problems.push(SettingsProblem {
+                    field: "satellites",
+                    severity: Severity::Error,
+                    message: format!(
+                        "satellite {id} starts with {} energy, outside of 0 to the max energy ({})",
+                        satellite.initial_energy, self.settings.max_energy
+                    ),
+                });
+            }
+        }
+
+        problems
+    }
+
+    pub fn to_string(&self, format: ScenarioFormat) -> String {
+        // Serializing plain structs to a string never fails.
+        match format {
+            ScenarioFormat::Toml => toml::to_string_pretty(self).unwrap_or_default(),
+            ScenarioFormat::Json => serde_json::to_string_pretty(self).unwrap_or_default(),
+        }
+    }
+
+    /// Read a scenario file, the format is picked from the extension
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(path: &std::path::Path) -> Result<Self, ScenarioError> {
+        let text = std::fs::read_to_string(path).map_err(|e| ScenarioError::Io(e.to_string()))?;
+        Self::parse(&text, ScenarioFormat::from_file_name(&path.to_string_lossy()))
+    }
+
+    /// Write a scenario file, the format is picked from the extension
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save(&self, path: &std::path::Path) -> Result<(), ScenarioError> {
+        let format = ScenarioFormat::from_file_name(&path.to_string_lossy());
+        std::fs::write(path, self.to_string(format)).map_err(|e| ScenarioError::Io(e.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scenario() -> Scenario {
+        let mut scenario = Scenario::new(Settings { boids: 5, ..Default::default() }, 42);
+        scenario.name = "two stations".to_string();
+        scenario.duration = Some(100);
+        scenario.ground_stations = vec![
+            GroundStation { name: "Arecibo".to_string(), longitude: -66.75 },
+            GroundStation { name: "Svalbard".to_string(), longitude: 15.4 },
+        ];
+        scenario
+    }
+
+    #[test]
+    fn round_trips_through_toml_and_json() {
+        let mut scenario = scenario();
+        scenario.satellites = scenario.satellite_definitions();
+        scenario.satellites[0].strategy = Strategy::NeverRelay;
+
+        for format in [ScenarioFormat::Toml, ScenarioFormat::Json] {
+            let text = scenario.to_string(format);
+            assert_eq!(Scenario::parse(&text, format).unwrap(), scenario);
+        }
+    }
+
+    #[test]
+    fn generates_satellites_from_the_seed() {
+        let scenario = scenario();
+        let satellites = scenario.satellite_definitions();
+        assert_eq!(satellites.len(), 5);
+        assert_eq!(satellites, scenario.satellite_definitions());
+
+        let other = Scenario { seed: 7, ..scenario.clone() };
+        assert_ne!(satellites, other.satellite_definitions());
+
+        // Shares only move the satellites to another orbit or strategy
+        let mut shared = scenario.clone();
+        shared.settings.leo_fraction = 1.0;
+        shared.settings.selfish_fraction = 1.0;
+        for (satellite, moved) in satellites.iter().zip(shared.satellite_definitions()) {
+            assert!(moved.orbit_distance < 1200.0);
+            assert_eq!(moved.strategy, Strategy::NeverRelay);
+            assert_eq!((moved.angle, moved.initial_energy), (satellite.angle, satellite.initial_energy));
+        }
+    }
+
+    #[test]
+    fn listed_satellites_set_the_count() {
+        let text = r#"
+            version = 1
+            seed = 3
+
+            [settings]
+            boids = 64
+            tick_interval_ms = 50
+            visible_range = 40.0
+            min_distance = 15.0
+            max_speed = 30.0
+            cohesion_factor = 0.2
+            separation_factor = 0.15
+            alignment_factor = 0.35
+            turn_speed_ratio = 0.33
+            border_margin = 0.1
+            color_adapt_factor = 0.05
+            energy_threshold = 33.0
+            cluster_distance = 75.0
+            comms_cost = 1.0
+            energy_gain = 1.5
+            max_energy = 100.0
+
+            [[satellites]]
+            orbit_distance = 36000.0
+            angle = 0.0
+            initial_energy = 80.0
+            strategy = "always_relay"
+        "#;
+
+        let scenario = Scenario::parse(text, ScenarioFormat::Toml).unwrap();
+        assert_eq!(scenario.settings.boids, 1);
+        assert_eq!(scenario.satellites[0].strategy, Strategy::AlwaysRelay);
+        assert_eq!(scenario.duration, None);
+        assert_eq!(scenario.epoch, default_epoch());
+        assert!(scenario.ground_stations.is_empty());
+    }
+
+    #[test]
+    fn parses_the_example_scenario() {
+        let scenario = Scenario::parse(include_str!("../scenarios/example.toml"), ScenarioFormat::Toml).unwrap();
+        assert_eq!(scenario.settings.boids, 3);
+        assert_eq!(scenario.satellites[2].strategy, Strategy::NeverRelay);
+        assert_eq!(scenario.ground_stations.len(), 2);
+        assert!(scenario.finished(500));
+        assert_eq!(scenario.epoch, Utc.with_ymd_and_hms(2024, 3, 20, 3, 6, 0).unwrap());
+    }
+
+    #[test]
+    fn rejects_invalid_scenarios() {
+        let mut scenario = scenario();
+        scenario.satellites = scenario.satellite_definitions();
+        scenario.satellites[1].initial_energy = 500.0;
+        scenario.settings.tick_interval_ms = 0;
+
+        let Err(ScenarioError::Invalid(errors)) = Scenario::parse(&scenario.to_string(ScenarioFormat::Toml), ScenarioFormat::Toml) else {
+            panic!("invalid scenario was accepted");
+        };
+        let fields = errors.iter().map(|p| p.field).collect::<Vec<_>>();
+        assert_eq!(fields, vec!["tick_interval_ms", "satellites"]);
+    }
+
+    #[test]
+    fn rejects_orbits_beyond_the_max_distance() {
+        let mut scenario = scenario();
+        scenario.satellites = scenario.satellite_definitions();
+        scenario.satellites[0].orbit_distance = MAX_DISTANCE;
+        assert!(scenario.validate().is_empty());
+
+        scenario.satellites[0].orbit_distance = MAX_DISTANCE + 1.0;
+        let problems = scenario.validate();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].message.contains("beyond the max distance"));
+    }
+
+    #[test]
+    fn rejects_newer_versions() {
+        let mut scenario = scenario();
+        scenario.version = SCENARIO_VERSION + 1;
+        let text = scenario.to_string(ScenarioFormat::Json);
+        assert_eq!(
+            Scenario::parse(&text, ScenarioFormat::Json),
+            Err(ScenarioError::UnsupportedVersion(SCENARIO_VERSION + 1))
+        );
+    }
+}
@@ -0,0 +1,138 @@
+//! This module orders the activities of a run by simulated time. Activities due
+//! at the same time run in the order of their kind and then in the order they
+//! were scheduled, so a run doesn't depend on timer jitter and always repeats.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct Entry<E> {
+    /// Simulated seconds since the epoch
+    time: f64,
+    event: E,
+    /// Breaks ties between equal events due at the same time
+    seq: u64,
+}
+
+impl<E: Ord> Ord for Entry<E> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // Reversed so the heap pops the earliest entry first
+        other
+            .time
+            .total_cmp(&self.time)
+            .then_with(|| other.event.cmp(&self.event))
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+impl<E: Ord> PartialOrd for Entry<E> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<E: Ord> PartialEq for Entry<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<E: Ord> Eq for Entry<E> {}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(bound(deserialize = "E: Ord + Deserialize<'de>"))]
+pub struct Scheduler<E> {
+    queue: BinaryHeap<Entry<E>>,
+    next_seq: u64,
+}
+
+/// Schedulers are equal when they hold the same events in the same order
+impl<E: Ord + Clone> PartialEq for Scheduler<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.next_seq == other.next_seq && self.queue.clone().into_sorted_vec() == other.queue.clone().into_sorted_vec()
+    }
+}
+
+impl<E: Ord> Default for Scheduler<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E: Ord> Scheduler<E> {
+    pub fn new() -> Self {
+        Self {
+            queue: BinaryHeap::new(),
+            next_seq: 0,
+        }
+    }
+
+    /// Schedule `event` at `time` simulated seconds since the epoch
+    pub fn schedule(&mut self, time: f64, event: E) {
+        self.queue.push(Entry { time, event, seq: self.next_seq });
+        self.next_seq += 1;
+    }
+
+    /// Time of the next event, if any
+    pub fn next_time(&self) -> Option<f64> {
+        self.queue.peek().map(|entry| entry.time)
+    }
+
+    /// Next event along with its time, without removing it
+    pub fn peek(&self) -> Option<(f64, &E)> {
+        self.queue.peek().map(|entry| (entry.time, &entry.event))
+    }
+
+    /// Remove the next event along with its time
+    pub fn pop(&mut self) -> Option<(f64, E)> {
+        self.queue.pop().map(|entry| (entry.time, entry.event))
+    }
+
+    /// Remove the next event if it is due at or before `time`
+    pub fn pop_until(&mut self, time: f64) -> Option<(f64, E)> {
+        if self.next_time()? <= time {
+            self.pop()
+        } else {
+            None
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.queue.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.queue.clear();
+        self.next_seq = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orders_by_time_kind_and_insertion() {
+        let mut scheduler = Scheduler::new();
+        scheduler.schedule(2.0, (1, "late"));
+        scheduler.schedule(1.0, (1, "second"));
+        scheduler.schedule(1.0, (0, "first"));
+        scheduler.schedule(1.0, (1, "second"));
+        scheduler.schedule(1.0, (1, "third"));
+
+        assert_eq!(scheduler.pop_until(1.5), Some((1.0, (0, "first"))));
+        assert_eq!(scheduler.pop_until(1.5), Some((1.0, (1, "second"))));
+        assert_eq!(scheduler.pop_until(1.5), Some((1.0, (1, "second"))));
+        assert_eq!(scheduler.pop_until(1.5), Some((1.0, (1, "third"))));
+        assert_eq!(scheduler.pop_until(1.5), None);
+        assert_eq!(scheduler.len(), 1);
+        assert_eq!(scheduler.pop(), Some((2.0, (1, "late"))));
+        assert!(scheduler.is_empty());
+    }
+}
@@ -1,5 +1,90 @@
+use std::fmt;
+
 use gloo::storage::{LocalStorage, Storage};
 use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Version of the settings stored by this build. Bump it when a field is
+/// added, renamed or changes meaning, and add a step to `Settings::migrate`.
+pub const SETTINGS_VERSION: u32 = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// The simulation runs, but probably not as intended
+    Warning,
+    /// The simulation can't run meaningfully with these settings
+    Error,
+}
+
+/// A problem with one of the settings, found by `Settings::validate`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SettingsProblem {
+    /// Name of the offending field
+    pub field: &'static str,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl SettingsProblem {
+    fn error(field: &'static str, message: impl Into<String>) -> Self {
+        Self { field, severity: Severity::Error, message: message.into() }
+    }
+
+    fn warning(field: &'static str, message: impl Into<String>) -> Self {
+        Self { field, severity: Severity::Warning, message: message.into() }
+    }
+}
+
+impl fmt::Display for SettingsProblem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+/// Steps upgrading the stored fields, the step at index `i` upgrades version `i + 1`
+/// to `i + 2`. Added fields don't need a step, they are filled with their defaults.
+const MIGRATIONS: [fn(&mut Map<String, Value>); SETTINGS_VERSION as usize - 1] = [
+    // Version 2 only added the version number
+    |_| (),
+    // Version 3 added `leo_fraction`, `geo_fraction` and `selfish_fraction`
+    |_| (),
+    // Version 4 added `game_interval_s` and `comms_interval_s`
+    |_| (),
+];
+
+/// Settings as they are kept in LocalStorage
+#[derive(Deserialize, Serialize)]
+struct StoredSettings {
+    version: u32,
+    settings: Value,
+}
+
+/// Explains why the settings in use are not exactly the ones that were stored
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SettingsNotice {
+    /// Stored by an earlier version, fields added since then use their defaults
+    Migrated { from: u32, defaulted: Vec<String> },
+    /// The stored settings couldn't be read and the defaults are used instead
+    Fallback { reason: String },
+}
+
+impl fmt::Display for SettingsNotice {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingsNotice::Migrated { from, defaulted } if defaulted.is_empty() => {
+                write!(f, "Saved settings were upgraded from version {from} to {SETTINGS_VERSION}.")
+            }
+            SettingsNotice::Migrated { from, defaulted } => write!(
+                f,
+                "Saved settings were upgraded from version {from} to {SETTINGS_VERSION}, new settings use their defaults: {}.",
+                defaulted.join(", ")
+            ),
+            SettingsNotice::Fallback { reason } => {
+                write!(f, "Saved settings couldn't be read and were reset to the defaults: {reason}.")
+            }
+        }
+    }
+}
 
 #[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
 pub struct Settings {
@@ -35,12 +120,221 @@ pub struct Settings {
     pub energy_gain: f32,
     /// Maximum energy per node
     pub max_energy: f32,
+    /// Share of the generated satellites moved to a low earth orbit
+    #[serde(default)]
+    pub leo_fraction: f32,
+    /// Share of the generated satellites moved to a geostationary orbit
+    #[serde(default)]
+    pub geo_fraction: f32,
+    /// Share of the generated satellites that never relay for their cluster
+    #[serde(default)]
+    pub selfish_fraction: f32,
+    /// Simulated seconds between game rounds
+    #[serde(default = "default_game_interval_s")]
+    pub game_interval_s: f32,
+    /// Simulated seconds between clustering rounds
+    #[serde(default = "default_comms_interval_s")]
+    pub comms_interval_s: f32,
+}
+
+fn default_game_interval_s() -> f32 {
+    333.0
+}
+
+fn default_comms_interval_s() -> f32 {
+    1000.0
 }
 impl Settings {
     const KEY: &'static str = "yew.boids.settings";
 
-    pub fn load() -> Self {
-        LocalStorage::get(Self::KEY).unwrap_or_default()
+    /// Settings stored in LocalStorage, upgraded to the current version. The
+    /// notice tells whether they had to be migrated or replaced by the defaults.
+    pub fn load() -> (Self, Option<SettingsNotice>) {
+        let Ok(stored) = LocalStorage::get::<Value>(Self::KEY) else {
+            // Nothing stored yet
+            return (Self::default(), None);
+        };
+
+        let (settings, notice) = Self::from_stored(stored);
+        if let Some(SettingsNotice::Migrated { .. }) = notice {
+            // Keep the upgraded settings so the notice is only shown once.
+            // Unreadable or newer settings are left for the version that wrote them.
+            settings.store();
+        }
+        (settings, notice)
+    }
+
+    /// Read stored settings of any version
+    pub fn from_stored(stored: Value) -> (Self, Option<SettingsNotice>) {
+        // Before version 2 the settings were stored without a version
+        let (version, value) = match serde_json::from_value::<StoredSettings>(stored.clone()) {
+            Ok(stored) => (stored.version, stored.settings),
+            Err(_) => (1, stored),
+        };
+
+        if version > SETTINGS_VERSION {
+            let reason = format!("they were saved by a newer version ({version})");
+            return (Self::default(), Some(SettingsNotice::Fallback { reason }));
+        }
+
+        let Value::Object(mut fields) = value else {
+            let reason = "they are not a settings object".to_string();
+            return (Self::default(), Some(SettingsNotice::Fallback { reason }));
+        };
+
+        Self::migrate(&mut fields, version);
+        let defaulted = Self::fill_defaults(&mut fields);
+
+        match serde_json::from_value::<Settings>(Value::Object(fields)) {
+            Ok(settings) if settings.has_errors() => {
+                let reason = settings.validate().iter().map(|p| p.to_string()).collect::<Vec<_>>().join(", ");
+                (Self::default(), Some(SettingsNotice::Fallback { reason }))
+            }
+            Ok(settings) if version == SETTINGS_VERSION && defaulted.is_empty() => (settings, None),
+            Ok(settings) => (settings, Some(SettingsNotice::Migrated { from: version, defaulted })),
+            Err(e) => (Self::default(), Some(SettingsNotice::Fallback { reason: e.to_string() })),
+        }
+    }
+
+    /// Check the settings for values and combinations the simulation can't run
+    /// with (errors) or that are likely mistakes (warnings).
+    pub fn validate(&self) -> Vec<SettingsProblem> {
+        let mut problems = Vec::new();
+
+        let floats = [
+            ("visible_range", self.visible_range),
+            ("min_distance", self.min_distance),
+            ("max_speed", self.max_speed),
+            ("cohesion_factor", self.cohesion_factor),
+            ("separation_factor", self.separation_factor),
+            ("alignment_factor", self.alignment_factor),
+            ("turn_speed_ratio", self.turn_speed_ratio),
+            ("border_margin", self.border_margin),
+            ("color_adapt_factor", self.color_adapt_factor),
+            ("energy_threshold", self.energy_threshold),
+            ("cluster_distance", self.cluster_distance),
+            ("comms_cost", self.comms_cost),
+            ("energy_gain", self.energy_gain),
+            ("max_energy", self.max_energy),
+            ("leo_fraction", self.leo_fraction),
+            ("geo_fraction", self.geo_fraction),
+            ("selfish_fraction", self.selfish_fraction),
+            ("game_interval_s", self.game_interval_s),
+            ("comms_interval_s", self.comms_interval_s),
+        ];
+        for (field, value) in floats {
+            if !value.is_finite() {
+                problems.push(SettingsProblem::error(field, "must be a finite number"));
+            } else if value < 0.0 {
+                problems.push(SettingsProblem::error(field, "can't be negative"));
+            }
+        }
+
+        if self.boids == 0 {
+            problems.push(SettingsProblem::error("boids", "there must be at least one satellite"));
+        }
+
+        for (field, value) in [
+            ("leo_fraction", self.leo_fraction),
+            ("geo_fraction", self.geo_fraction),
+            ("selfish_fraction", self.selfish_fraction),
+        ] {
+            if value > 1.0 {
+                problems.push(SettingsProblem::error(field, "is a share and can't be above 100%"));
+            }
+        }
+
+        if self.leo_fraction + self.geo_fraction > 1.0 {
+            problems.push(SettingsProblem::error(
+                "geo_fraction",
+                format!("is above the share left by low earth orbits ({:.0}%)", (1.0 - self.leo_fraction) * 100.0),
+            ));
+        }
+
+        if self.tick_interval_ms == 0 {
+            problems.push(SettingsProblem::error("tick_interval_ms", "must be at least 1 ms"));
+        } else if self.tick_interval_ms < 16 {
+            problems.push(SettingsProblem::warning(
+                "tick_interval_ms",
+                "ticks faster than the screen refreshes only use more CPU",
+            ));
+        }
+
+        for (field, value) in [("game_interval_s", self.game_interval_s), ("comms_interval_s", self.comms_interval_s)] {
+            if value == 0.0 {
+                problems.push(SettingsProblem::error(field, "must be above zero"));
+            }
+        }
+
+        if self.max_energy == 0.0 {
+            problems.push(SettingsProblem::error("max_energy", "must be above zero"));
+        }
+
+        if self.cluster_distance == 0.0 {
+            problems.push(SettingsProblem::error("cluster_distance", "must be above zero"));
+        }
+
+        if self.max_energy < self.energy_threshold {
+            problems.push(SettingsProblem::error(
+                "energy_threshold",
+                format!("is above the max energy ({}), no satellite can become a cluster head", self.max_energy),
+            ));
+        }
+
+        if self.comms_cost > self.max_energy {
+            problems.push(SettingsProblem::error(
+                "comms_cost",
+                format!("is above the max energy ({}), no satellite can ever relay", self.max_energy),
+            ));
+        } else if self.comms_cost > self.energy_threshold {
+            problems.push(SettingsProblem::warning(
+                "comms_cost",
+                format!("is above the energy threshold ({}), new cluster heads may not afford to relay", self.energy_threshold),
+            ));
+        }
+
+        if self.energy_gain < self.comms_cost {
+            problems.push(SettingsProblem::warning(
+                "energy_gain",
+                format!("is below the comms cost ({}), relaying never pays back and energy drains", self.comms_cost),
+            ));
+        }
+
+        problems
+    }
+
+    /// Whether `validate` finds any errors
+    pub fn has_errors(&self) -> bool {
+        self.validate().iter().any(|p| p.severity == Severity::Error)
+    }
+
+    /// Problems found by `validate` with `field`
+    pub fn problems_with(problems: &[SettingsProblem], field: &str) -> Vec<SettingsProblem> {
+        problems.iter().filter(|p| p.field == field).cloned().collect()
+    }
+
+    /// Bring fields stored by `version` up to the current version, one version at a time
+    fn migrate(fields: &mut Map<String, Value>, version: u32) {
+        let first = version.max(1) as usize - 1;
+        for step in &MIGRATIONS[first..] {
+            step(fields);
+        }
+    }
+
+    /// Add the default of every missing field, returning their names
+    fn fill_defaults(fields: &mut Map<String, Value>) -> Vec<String> {
+        let Ok(Value::Object(defaults)) = serde_json::to_value(Self::default()) else {
+            return Vec::new();
+        };
+
+        let mut defaulted = Vec::new();
+        for (key, value) in defaults {
+            if !fields.contains_key(&key) {
+                defaulted.push(key.clone());
+                fields.insert(key, value);
+            }
+        }
+        defaulted
     }
 
     pub fn remove() {
@@ -48,7 +342,14 @@ impl Settings {
     }
 
     pub fn store(&self) {
-        let _ = LocalStorage::set(Self::KEY, self);
+        let _ = LocalStorage::set(Self::KEY, self.to_stored());
+    }
+
+    /// Settings tagged with the current version, as they are stored
+    pub fn to_stored(&self) -> Value {
+        // Serializing plain structs never fails.
+        let settings = serde_json::to_value(self).unwrap_or_default();
+        serde_json::to_value(StoredSettings { version: SETTINGS_VERSION, settings }).unwrap_or_default()
     }
 }
 impl Default for Settings {
@@ -70,6 +371,88 @@ impl Default for Settings {
             comms_cost: 1.0,
             energy_gain: 1.5,
             max_energy: 100.0,
+            leo_fraction: 0.0,
+            geo_fraction: 0.0,
+            selfish_fraction: 0.0,
+            game_interval_s: default_game_interval_s(),
+            comms_interval_s: default_comms_interval_s(),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn stored(settings: &Settings) -> Value {
+        settings.to_stored()
+    }
+
+    #[test]
+    fn reads_current_version_without_notice() {
+        let settings = Settings { boids: 12, ..Default::default() };
+        assert_eq!(Settings::from_stored(stored(&settings)), (settings, None));
+    }
+
+    #[test]
+    fn migrates_unversioned_settings_keeping_their_values() {
+        let mut legacy = serde_json::to_value(Settings { boids: 12, ..Default::default() }).unwrap();
+        legacy.as_object_mut().unwrap().remove("max_energy");
+
+        let (settings, notice) = Settings::from_stored(legacy);
+        assert_eq!(settings.boids, 12);
+        assert_eq!(settings.max_energy, Settings::default().max_energy);
+        assert_eq!(
+            notice,
+            Some(SettingsNotice::Migrated { from: 1, defaulted: vec!["max_energy".to_string()] })
+        );
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        assert!(Settings::default().validate().is_empty());
+    }
+
+    #[test]
+    fn reports_invalid_combinations() {
+        let settings = Settings {
+            tick_interval_ms: 0,
+            energy_threshold: 120.0,
+            comms_cost: 150.0,
+            energy_gain: 0.5,
+            ..Default::default()
+        };
+        let problems = settings.validate();
+        let severity = |field| Settings::problems_with(&problems, field).first().map(|p| p.severity);
+
+        assert!(settings.has_errors());
+        assert_eq!(severity("tick_interval_ms"), Some(Severity::Error));
+        assert_eq!(severity("energy_threshold"), Some(Severity::Error));
+        assert_eq!(severity("comms_cost"), Some(Severity::Error));
+        assert_eq!(severity("energy_gain"), Some(Severity::Warning));
+        assert_eq!(severity("boids"), None);
+
+        let warned = Settings { comms_cost: 2.0, ..Default::default() };
+        assert!(!warned.has_errors());
+        assert_eq!(warned.validate()[0].field, "energy_gain");
+    }
+
+    #[test]
+    fn falls_back_to_defaults() {
+        let newer = json!({ "version": SETTINGS_VERSION + 1, "settings": {} });
+        let (settings, notice) = Settings::from_stored(newer);
+        assert_eq!(settings, Settings::default());
+        assert!(matches!(notice, Some(SettingsNotice::Fallback { .. })));
+
+        let invalid = json!({ "version": SETTINGS_VERSION, "settings": { "boids": "many" } });
+        let (settings, notice) = Settings::from_stored(invalid);
+        assert_eq!(settings, Settings::default());
+        assert!(matches!(notice, Some(SettingsNotice::Fallback { .. })));
+
+        let unusable = stored(&Settings { tick_interval_ms: 0, ..Default::default() });
+        let (settings, notice) = Settings::from_stored(unusable);
+        assert_eq!(settings, Settings::default());
+        assert!(matches!(notice, Some(SettingsNotice::Fallback { .. })));
+    }
+}
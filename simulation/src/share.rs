@@ -0,0 +1,142 @@
+//! This module encodes the scenario of a run into the fragment of the page URL,
+//! so a run can be reproduced by opening a link. The settings are kept with
+//! their version so links made by earlier versions can be migrated, and the
+//! payload is deflated and base64 encoded to keep links short.
+
+use std::fmt;
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use serde_json::Value;
+
+use crate::scenario::{Scenario, ScenarioFormat};
+use crate::settings::{Settings, SettingsNotice};
+
+/// Key of the shared run in the URL fragment, e.g. `#run=...`
+const FRAGMENT_KEY: &str = "run=";
+const COMPRESSION_LEVEL: u8 = 9;
+/// Largest scenario read from a link, links are far smaller but a crafted one could inflate to any size
+const MAX_SCENARIO_BYTES: usize = 1 << 20;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ShareError {
+    Encoding(String),
+    Compression,
+    /// The scenario inflates to more than `MAX_SCENARIO_BYTES`
+    TooLarge,
+    Contents(String),
+}
+
+impl fmt::Display for ShareError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShareError::Encoding(e) => write!(f, "the link is not encoded correctly: {e}"),
+            ShareError::Compression => write!(f, "the link is truncated or corrupted"),
+            ShareError::TooLarge => write!(f, "the link holds more than {} KiB", MAX_SCENARIO_BYTES / 1024),
+            ShareError::Contents(e) => write!(f, "the link doesn't describe a run: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for ShareError {}
+
+/// Scenario restored from a link
+#[derive(Clone, Debug, PartialEq)]
+pub struct Shared {
+    pub scenario: Scenario,
+    /// Set when the settings were made by an earlier version or couldn't be used
+    pub notice: Option<SettingsNotice>,
+}
+
+pub fn encode(scenario: &Scenario) -> String {
+    // Serializing plain structs never fails.
+    let mut run = serde_json::to_value(scenario).unwrap_or_default();
+    // Settings as they are stored, see `Settings::to_stored`
+    run["settings"] = scenario.settings.to_stored();
+    let json = serde_json::to_vec(&run).unwrap_or_default();
+    URL_SAFE_NO_PAD.encode(miniz_oxide::deflate::compress_to_vec(&json, COMPRESSION_LEVEL))
+}
+
+pub fn decode(encoded: &str) -> Result<Shared, ShareError> {
+    let compressed = URL_SAFE_NO_PAD
+        .decode(encoded.trim())
+        .map_err(|e| ShareError::Encoding(e.to_string()))?;
+    let json = miniz_oxide::inflate::decompress_to_vec_with_limit(&compressed, MAX_SCENARIO_BYTES).map_err(|e| {
+        match e.status {
+            miniz_oxide::inflate::TINFLStatus::HasMoreOutput => ShareError::TooLarge,
+            _ => ShareError::Compression,
+        }
+    })?;
+    let mut run: Value = serde_json::from_slice(&json).map_err(|e| ShareError::Contents(e.to_string()))?;
+    let Some(run_fields) = run.as_object_mut() else {
+        return Err(ShareError::Contents("it is not a scenario object".to_string()));
+    };
+
+    let (settings, notice) = Settings::from_stored(run_fields.remove("settings").unwrap_or_default());
+    run_fields.insert("settings".to_string(), serde_json::to_value(settings).unwrap_or_default());
+    let scenario = Scenario::parse(&run.to_string(), ScenarioFormat::Json).map_err(|e| ShareError::Contents(e.to_string()))?;
+    Ok(Shared { scenario, notice })
+}
+
+/// Link to the current page reproducing a run of `scenario`
+pub fn link(scenario: &Scenario) -> String {
+    let location = gloo::utils::window().location();
+    let page = location.href().unwrap_or_default();
+    let page = page.split('#').next().unwrap_or_default();
+    format!("{page}#{FRAGMENT_KEY}{}", encode(scenario))
+}
+
+/// Run shared through the URL of the page, if it was opened from a link
+pub fn from_location() -> Option<Result<Shared, ShareError>> {
+    let hash = gloo::utils::window().location().hash().ok()?;
+    let encoded = hash.trim_start_matches('#').strip_prefix(FRAGMENT_KEY)?;
+    Some(decode(encoded))
+}
+
+/// Remove the shared run from the URL, so reloading the page uses the stored settings
+pub fn clear_location() {
+    let location = gloo::utils::window().location();
+    let path = format!(
+        "{}{}",
+        location.pathname().unwrap_or_default(),
+        location.search().unwrap_or_default()
+    );
+    let _ = gloo::utils::history().replace_state_with_url(&wasm_bindgen::JsValue::NULL, "", Some(&path));
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+    use crate::scenario::GroundStation;
+
+    #[test]
+    fn round_trips_scenarios() {
+        let settings = Settings { boids: 2, selfish_fraction: 0.25, ..Default::default() };
+        let mut scenario = Scenario::new(settings, 987_654_321);
+        scenario.name = "Two satellites".to_string();
+        scenario.epoch = Utc.with_ymd_and_hms(2024, 3, 20, 3, 6, 0).unwrap();
+        scenario.satellites = scenario.satellite_definitions();
+        scenario.ground_stations.push(GroundStation { name: "Kiruna".to_string(), longitude: 20.2 });
+        let encoded = encode(&scenario);
+
+        assert!(encoded.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'));
+        assert_eq!(decode(&encoded).unwrap(), Shared { scenario, notice: None });
+    }
+
+    #[test]
+    fn rejects_corrupted_links() {
+        let encoded = encode(&Scenario::new(Settings::default(), 1));
+
+        assert!(matches!(decode("not base64!"), Err(ShareError::Encoding(_))));
+        assert_eq!(decode(&encoded[..encoded.len() / 8 * 4]), Err(ShareError::Compression));
+        let not_a_run = URL_SAFE_NO_PAD.encode(miniz_oxide::deflate::compress_to_vec(br#"{"seed": "one"}"#, 6));
+        assert!(matches!(decode(&not_a_run), Err(ShareError::Contents(_))));
+
+        // A few bytes inflating to a huge payload are refused before they are read
+        let bomb = URL_SAFE_NO_PAD.encode(miniz_oxide::deflate::compress_to_vec(&vec![b' '; 4 * MAX_SCENARIO_BYTES], 10));
+        assert!(bomb.len() < 32 * 1024);
+        assert_eq!(decode(&bomb), Err(ShareError::TooLarge));
+    }
+}
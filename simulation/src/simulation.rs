@@ -1,100 +1,165 @@
-use gloo::timers::callback::Interval;
-use yew::{html, Callback, Component, Context, Html, Properties};
+use std::borrow::Cow;
+use std::rc::Rc;
 
-use crate::cluster::{Cluster, ClusterMap};
-use crate::components::info_panel;
-use crate::math::Vector2D;
-use crate::quadtree::{box2d::Box2d, quadtree::QuadTree, types::*};
-use crate::satellite::{SatelliteEnergy, SatellitePosition, SatelliteProperties};
+use chrono::{DateTime, Utc};
+use gloo::timers::callback::Interval;
+use web_sys::HtmlInputElement;
+use yew::{html, Callback, Component, Context, Html, InputEvent, Properties, TargetCast};
+
+use crate::clock::{self, SimClock, DEFAULT_TIME_WARP};
+use crate::cluster::ClusterMap;
+use crate::components::{ground_station, info_panel, qtree_stats, query_overlay};
+use crate::engine::{Activity, Engine, Stop};
+use crate::math::{Vector2D, TAU};
+use crate::metrics::Report;
+use crate::recorder::{Frame, Recorder};
+use crate::satellite::{SatelliteEnergy, SatellitePosition, SatelliteProperties, EARTH_ROTATION};
 use crate::satellite;
-use crate::settings::Settings;
+use crate::scenario::Scenario;
+use crate::snapshot::Snapshot;
 
 pub const SIZE: Vector2D = Vector2D::new(1200.0, 1200.0);
 
+/// Most activities run in one tick, the simulated clock falls behind the time warp
+/// rather than stalling the page when more are due
+const MAX_ACTIVITIES_PER_TICK: usize = 256;
+/// Most activities run in one tick when running until a condition as fast as possible
+const MAX_ACTIVITIES_PER_RUN: usize = 2048;
+
 #[derive(Debug)]
 pub enum Msg {
     Tick,
-    CommsTick,
-    GameTick,
     ClickedSat(usize),
+    /// Show the recorded frame at this index
+    Scrub(usize),
+    ToggleReplay,
+    GoLive,
+}
+
+/// Condition ending a run started from the panel
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RunUntil {
+    Time(DateTime<Utc>),
+    FirstDepletion,
+    /// The satellites relay this many more times
+    Relays(u64),
+}
+
+/// Request from the panel to inspect the run
+#[derive(Clone, Debug)]
+pub enum Control {
+    /// Run up to and including the next activity of this kind
+    Step(Activity),
+    /// Run as fast as possible until the condition, whether paused or not
+    RunUntil(RunUntil),
+    /// Send the state of the run to `on_snapshot`
+    Snapshot,
+    /// Continue from a snapshot
+    Restore(Rc<Snapshot>),
+}
+
+impl PartialEq for Control {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Control::Step(a), Control::Step(b)) => a == b,
+            (Control::RunUntil(a), Control::RunUntil(b)) => a == b,
+            (Control::Snapshot, Control::Snapshot) => true,
+            // Snapshots are large, the same request holds the same one
+            (Control::Restore(a), Control::Restore(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Properties)]
 pub struct Props {
-    pub settings: Settings,
+    pub scenario: Scenario,
     #[prop_or_default]
     pub generation: usize,
     #[prop_or_default]
     pub paused: bool,
     #[prop_or(false)]
     pub show_qtree: bool,
+    /// Simulated seconds per second of real time
+    #[prop_or(DEFAULT_TIME_WARP)]
+    pub time_warp: f32,
+    /// Latest request from the panel along with a counter, so sending the same request again is noticed
+    #[prop_or_default]
+    pub control: Option<(usize, Control)>,
+    /// Receives the metrics sampled at the end of every game round
+    #[prop_or_default]
+    pub on_report: Callback<Report>,
+    /// Receives the state of the run when asked with `Control::Snapshot`
+    #[prop_or_default]
+    pub on_snapshot: Callback<Snapshot>,
+}
+
+/// A recorded tick shown instead of the live run, the run doesn't advance meanwhile
+struct Replay {
+    /// Index of the frame in the recorder
+    frame: usize,
+    playing: bool,
+    clock: SimClock,
+    /// Game round of the frame, the ledgers only show the changes up to it
+    game_round: u64,
+    props: Vec<SatelliteProperties>,
+    positions: Vec<SatellitePosition>,
+    energy: Vec<SatelliteEnergy>,
+    cluster_map: ClusterMap,
+}
+
+impl Replay {
+    fn new(engine: &Engine) -> Self {
+        Self {
+            frame: 0,
+            playing: false,
+            clock: SimClock::new(engine.clock().epoch()),
+            game_round: 0,
+            props: engine.props().to_vec(),
+            positions: engine.positions().to_vec(),
+            energy: engine.energy().to_vec(),
+            cluster_map: ClusterMap::new(),
+        }
+    }
+
+    fn show(&mut self, index: usize, frame: &Frame) {
+        self.frame = index;
+        self.clock = SimClock::new(self.clock.epoch());
+        self.clock.advance_to(frame.elapsed_secs);
+        self.game_round = frame.game_round;
+        self.cluster_map = frame.apply(&mut self.props, &mut self.positions, &mut self.energy);
+    }
 }
 
 pub struct Simulation {
-    entity_props: Vec<SatelliteProperties>,
-    entity_positions: Vec<SatellitePosition>,
-    entity_energy: Vec<SatelliteEnergy>,
+    engine: Engine,
+    /// Only paces the animation, the order of the activities is kept by the engine
     interval: Interval,
-    comms_interval: Interval,
-    game_interval: Interval,
     generation: usize,
-    qtree: Option<QuadTree<usize>>,
     show_qtree: bool,
     selected_satellite_id: Option<usize>,
-    cluster_map: ClusterMap,
+    /// Set while running until a condition
+    running_until: Option<Stop>,
+    /// The latest ticks of the run, for the timeline
+    recorder: Recorder,
+    replay: Option<Replay>,
 }
 impl Component for Simulation {
     type Message = Msg;
     type Properties = Props;
 
     fn create(ctx: &Context<Self>) -> Self {
-        let settings = ctx.props().settings.clone();
-
-        let mut entity_props = Vec::with_capacity(settings.boids);
-        let mut entity_positions = Vec::with_capacity(settings.boids);
-        let mut entity_energy = Vec::with_capacity(settings.boids);
-
-        for id in 0..settings.boids {
-            let properties = SatelliteProperties::new_random(id);
-            let position = SatellitePosition::new_random(&properties);
-            let game = SatelliteEnergy::new_random(id, &settings);
-
-            entity_props.push(properties);
-            entity_positions.push(position);
-            entity_energy.push(game);
-        }
-
-        let interval = {
-            let link = ctx.link().clone();
-            Interval::new(settings.tick_interval_ms as u32, move || {
-                link.send_message(Msg::Tick)
-            })
-        };
-
-        let comms_interval = {
-            let link = ctx.link().clone();
-            Interval::new(1000_u32, move || link.send_message(Msg::CommsTick))
-        };
-
-        let game_interval = {
-            let link = ctx.link().clone();
-            Interval::new(333_u32, move || link.send_message(Msg::GameTick))
-        };
-
-        let generation = ctx.props().generation;
+        let scenario = &ctx.props().scenario;
 
         Self {
-            entity_props,
-            entity_positions,
-            entity_energy,
-            interval,
-            comms_interval,
-            game_interval,
-            generation,
-            qtree: None,
+            engine: Engine::new(scenario.clone()),
+            interval: tick_interval(ctx),
+            generation: ctx.props().generation,
             show_qtree: false,
             selected_satellite_id: None,
-            cluster_map: ClusterMap::new(),
+            running_until: None,
+            recorder: Recorder::default(),
+            replay: None,
         }
     }
 
@@ -102,204 +167,79 @@ impl Component for Simulation {
         match msg {
             Msg::Tick => {
                 let Props {
-                    ref settings,
+                    ref scenario,
                     paused,
+                    time_warp,
+                    ref on_report,
                     ..
                 } = *ctx.props();
 
-                if paused {
-                    false
-                } else {
-                    // update entity position
-                    for (pos, props) in self
-                        .entity_positions
-                        .iter_mut()
-                        .zip(self.entity_props.iter())
-                    {
-                        pos.update(props, settings);
+                if let Some(replay) = &mut self.replay {
+                    if !replay.playing {
+                        return false;
+                    }
+                    // Replay forwards until the latest frame, then continue the run
+                    match self.recorder.get(replay.frame + 1) {
+                        Some(frame) => replay.show(replay.frame + 1, frame),
+                        None => self.replay = None,
                     }
-
                     true
-                }
-            }
-            Msg::GameTick => {
-                let Props {
-                    paused,
-                    ..
-                } = *ctx.props();
-
-                if paused {
+                } else if let Some(stop) = self.running_until {
+                    if self.engine.run_until_stop(stop, MAX_ACTIVITIES_PER_RUN, |report| on_report.emit(report)) {
+                        self.running_until = None;
+                    }
+                    self.recorder.record(Frame::capture(&self.engine));
+                    true
+                } else if paused || self.engine.finished() {
                     false
                 } else {
-                    for cluster in self.cluster_map.clusters() {
-                        if cluster.size() < 2 {
-                            continue;
-                        }
-
-                        for &id in cluster.members() {
-                            self.entity_energy.get_mut(id).expect("Couldn't get sat in cluster").update_game(cluster);
-                        }
-                        
-                        // All sats in cluster should've made a decision to enter or leave
-                        
-                        for id in 0..cluster.size() {
-                            let (first, sec) = cluster.members().split_at(id);
-                            let (current, other) = sec.split_at(1);
-                            let sat_ptr  = self.entity_energy.as_mut_ptr();
-                            unsafe {
-                                let neighbors = other.iter().chain(first).filter_map(|&eid| sat_ptr.add(eid).as_ref()).collect::<Vec<_>>();
-                                self.entity_energy.get_mut(current[0]).unwrap().update(neighbors);
-                            }
-                        }
-                    }
- 
+                    let until = self.engine.clock().elapsed_secs()
+                        + clock::warped_secs(scenario.settings.tick_interval_ms, time_warp);
+                    self.engine.run_until(until, MAX_ACTIVITIES_PER_TICK, |report| on_report.emit(report));
+                    self.recorder.record(Frame::capture(&self.engine));
                     true
                 }
             }
             Msg::ClickedSat(id) => {
                 if self.selected_satellite_id == Some(id) {
                     self.selected_satellite_id = None;
-                    self.entity_props[id].set_selected(false);
+                    self.set_selected(id, false);
                 } else {
-                    if self.selected_satellite_id.is_some() {
-                        let prev_id = self.selected_satellite_id.unwrap();
-                        self.entity_props[prev_id].set_selected(false);
+                    if let Some(prev_id) = self.selected_satellite_id {
+                        self.set_selected(prev_id, false);
                     }
                     self.selected_satellite_id = Some(id);
-                    self.entity_props[id].set_selected(true);
+                    self.set_selected(id, true);
                 }
                 true
             }
-            Msg::CommsTick => {
-                let Props {
-                    ref settings,
-                    paused,
-                    ..
-                } = *ctx.props();
-
-                if paused {
-                    false
-                } else {
-                    // Create quadtree
-                    let mut qtree = QuadTree::new(
-                        Box2d::new(Point::new(0.0, SIZE.y), Point::new(SIZE.x, 0.0)),
-                        4,
-                    );
-
-                    // Build quadtree for efficient Entity search
-                    for (id, entity) in self.entity_positions.iter().enumerate() {
-                        let position = entity.screen_position();
-                        qtree.insert(Point::new(position.x, position.y), id);
-                    }
-
-                    // Iterate through all satellites to create a list of cluster head candidates based on energy if exceeds threshold
-
-                    let cluster_head_candidates = self
-                        .entity_energy
-                        .iter()
-                        .enumerate()
-                        .filter_map(|(id, energy)| {
-                            if energy.energy() > settings.energy_threshold {
-                                Some(id)
-                            } else {
-                                None
-                            }
-                        })
-                        .collect::<Vec<_>>();
-
-                    // Consolidate cluster heads based on distance
-
-                    let mut cluster_heads: Vec<usize> = Vec::new();
-
-                    for id in cluster_head_candidates {
-                        let position = self.entity_positions[id].screen_position();
-                        let mut is_cluster_head = true;
-
-                        for head in &cluster_heads {
-                            let head_pos = self.entity_positions[*head].screen_position();
-                            let distance = (position - head_pos).magnitude();
-
-                            if distance < settings.cluster_distance {
-                                is_cluster_head = false;
-                                break;
-                            }
-                        }
-
-                        if is_cluster_head {
-                            cluster_heads.push(id);
-                        }
-                    }
-
-                    // Create edge list of members to their nearest cluster heads
-                    let mut clusters = ClusterMap::new();
-
-                    // Create clusters using cluster heads
-                    for ch_id in cluster_heads.iter() {
-                        let mut cluster = Cluster::new(*ch_id);
-                        if let Some(prev_cluster) = self.cluster_map.get(cluster.head()) {
-                            cluster.set_color(prev_cluster.color());
-                            self.entity_props[cluster.head()].set_color(prev_cluster.color());
-                        } else {
-                            cluster.set_color(self.entity_props[cluster.head()].color());
-                        }
-                        clusters.insert(cluster);
-                    }
-
-                    // Assign members to the nearest cluster head
-                    for prop in self.entity_props.iter_mut() {
-                        if cluster_heads.contains(&prop.id()) {
-                            // skip assignment for cluster heads
-                            continue;
-                        }
-
-                        let id = prop.id(); 
-                        let pos = self.entity_positions.get(id).unwrap();
-                        let position = pos.screen_position();
-                        let mut nearest_distance = f32::INFINITY;
-                        let mut nearest_head = None;
-
-                        for head in &cluster_heads {
-                            let head_pos = self.entity_positions[*head].screen_position();
-                            let distance = (position - head_pos).magnitude();
-
-                            if distance < nearest_distance {
-                                nearest_distance = distance;
-                                nearest_head = Some(*head);
-                            }
-                        }
-
-                        if let Some(head) = nearest_head {
-                            let cluster = clusters.get_mut(head).unwrap();
-                            cluster.add_member(id);
-                        }
-                    }
-
-                    // Set cluster colors to the average color of all members
-                    for cluster in clusters.clusters_mut() {
-                        if cluster.size() < 2 {
-                            continue;
-                        }
-
-                        // Mix member colors
-                        let mut member_color = cluster.members().iter().map(|id| self.entity_props[*id].color()).sum::<f32>();
-                        member_color /= cluster.members().len() as f32;
-                        let head_color = cluster.color();
-                        let mut color = (head_color + member_color) / 2.0;
-                        color %= 360.0;
-
-                        // Set color
-                        cluster.set_color(color);
-                        self.entity_props[cluster.head()].set_color(color);
-                        for member in cluster.members() {
-                            self.entity_props[*member].set_color(color);
+            Msg::Scrub(index) => {
+                let Some(frame) = self.recorder.get(index) else {
+                    return false;
+                };
+                let replay = self.replay.get_or_insert_with(|| Replay::new(&self.engine));
+                replay.playing = false;
+                replay.show(index, frame);
+                true
+            }
+            Msg::ToggleReplay => {
+                match &mut self.replay {
+                    Some(replay) => replay.playing = !replay.playing,
+                    // Replay the whole recording
+                    None => {
+                        if let Some(frame) = self.recorder.get(0) {
+                            let mut replay = Replay::new(&self.engine);
+                            replay.playing = true;
+                            replay.show(0, frame);
+                            self.replay = Some(replay);
                         }
                     }
-
-                    self.cluster_map = clusters;
-                    self.qtree = Some(qtree);
-
-                    true
                 }
+                true
+            }
+            Msg::GoLive => {
+                self.replay = None;
+                true
             }
         }
     }
@@ -312,58 +252,30 @@ impl Component for Simulation {
         }
 
         let should_reset =
-            old_props.settings != props.settings || self.generation != props.generation;
+            old_props.scenario != props.scenario || self.generation != props.generation;
 
         self.generation = props.generation;
 
         if should_reset {
-            // Clear entity info
-            self.entity_props.clear();
-            self.entity_positions.clear();
-            self.entity_energy.clear();
-
             self.selected_satellite_id = None;
-            self.cluster_map = ClusterMap::new();
-
-            let settings = &props.settings;
-
-            // Generate new entities
-            for id in 0..settings.boids {
-                let properties = SatelliteProperties::new_random(id);
-                let position = SatellitePosition::new_random(&properties);
-                let game = SatelliteEnergy::new_random(id, settings);
+            self.running_until = None;
+            self.recorder.clear();
+            self.replay = None;
 
-                self.entity_props.push(properties);
-                self.entity_positions.push(position);
-                self.entity_energy.push(game);
-            }
+            // Start over, restarting the random number generator so the run repeats
+            self.engine = Engine::new(props.scenario.clone());
 
             // as soon as the previous task is dropped it is cancelled.
             // We don't need to worry about manually stopping it.
-            self.interval = {
-                let link = ctx.link().clone();
-                Interval::new(settings.tick_interval_ms as u32, move || {
-                    link.send_message(Msg::Tick)
-                })
-            };
+            self.interval = tick_interval(ctx);
+        }
 
-            // as soon as the previous task is dropped it is cancelled.
-            // We don't need to worry about manually stopping it.
-            self.comms_interval = {
-                let link = ctx.link().clone();
-                Interval::new(1000_u32, move || {
-                    link.send_message(Msg::CommsTick)
-                })
-            };
-
-            self.game_interval = {
-                let link = ctx.link().clone();
-                Interval::new(333_u32, move || link.send_message(Msg::GameTick))
-            };
-
-            true
-        } else {
-            false
+        match &props.control {
+            Some((_, control)) if props.control != old_props.control => {
+                self.apply_control(control.clone(), props);
+                true
+            }
+            _ => should_reset,
         }
     }
 
@@ -371,28 +283,129 @@ impl Component for Simulation {
         let view_box = format!("0 0 {} {}", SIZE.x, SIZE.y);
         let link = ctx.link().clone();
         let onclick_cb = Callback::from(move |id| link.send_message(Msg::ClickedSat(id)));
-        let num_sats = self.entity_props.len();
+        let engine = &self.engine;
+        let (entity_props, entity_positions, entity_energy, cluster_map, clock) = match &self.replay {
+            Some(replay) => (&replay.props[..], &replay.positions[..], &replay.energy[..], &replay.cluster_map, &replay.clock),
+            None => (engine.props(), engine.positions(), engine.energy(), engine.cluster_map(), engine.clock()),
+        };
+        let ledger = |id: usize| match &self.replay {
+            Some(replay) => Cow::Owned(replay.energy[id].ledger().until(replay.game_round)),
+            None => Cow::Borrowed(engine.energy()[id].ledger()),
+        };
+        let rotation = (clock.elapsed_secs() * EARTH_ROTATION as f64 % TAU as f64) as f32;
+        let time = match self.replay {
+            Some(_) => format!("{} (replay)", clock.now().format("%Y-%m-%d %H:%M:%S UTC")),
+            None => format!("{} (×{})", clock.now().format("%Y-%m-%d %H:%M:%S UTC"), ctx.props().time_warp),
+        };
 
         html! {
-            <svg class="simulation-window" viewBox={view_box} preserveAspectRatio="xMidYMid">
+            <>
+                <svg class="simulation-window" viewBox={view_box} preserveAspectRatio="xMidYMid">
 
-                { self.cluster_map.clusters().iter().map(|e| crate::cluster::render(e, &self.entity_positions)).collect::<Vec<_>>() }
+                    { engine.scenario().ground_stations.iter().map(|station| ground_station::render(station, rotation)).collect::<Html>() }
 
-                { (0..num_sats).map(|id| {
-                    satellite::render(&self.entity_props[id], &self.entity_positions[id], &self.entity_energy[id], onclick_cb.clone())
-                }).collect::<Html>() }
+                    { cluster_map.clusters().iter().map(|e| crate::cluster::render(e, entity_positions)).collect::<Vec<_>>() }
 
-                if let Some(id) = self.selected_satellite_id {
-                    { info_panel::render(&self.entity_props[id], &self.entity_positions[id], &self.entity_energy[id]) }
-                }
+                    { (0..entity_props.len()).map(|id| {
+                        satellite::render(&entity_props[id], &entity_positions[id], &entity_energy[id], onclick_cb.clone())
+                    }).collect::<Html>() }
 
-                if let Some(qtree) = self.qtree.as_ref() {
-                    if self.show_qtree {
-                        { qtree.render() }
+                    if let Some(id) = self.selected_satellite_id {
+                        { info_panel::render(&entity_props[id], &entity_positions[id], &entity_energy[id], &ledger(id)) }
                     }
+
+                    // The quadtree is only kept for the live run
+                    if self.show_qtree && self.replay.is_none() {
+                        { engine.qtree().render() }
+                        { qtree_stats::render(&engine.qtree().stats()) }
+
+                        // Highlight the head search made for the selected satellite
+                        if let Some(query) = self.selected_satellite_id.and_then(|id| engine.head_query(id)) {
+                            { query_overlay::render(query) }
+                        }
+                    }
+
+                    <text x="16" y={(SIZE.y - 16.0).to_string()} font-weight="bold" fill="white">{ time }</text>
+
+                </svg>
+                { self.view_timeline(ctx) }
+            </>
+        }
+    }
+}
+
+impl Simulation {
+    fn apply_control(&mut self, control: Control, props: &Props) {
+        self.running_until = None;
+        self.replay = None;
+        match control {
+            Control::Step(activity) => {
+                self.engine.step_until(activity, |report| props.on_report.emit(report));
+                self.recorder.record(Frame::capture(&self.engine));
+            }
+            Control::RunUntil(RunUntil::Time(time)) => {
+                let since_epoch = time - self.engine.clock().epoch();
+                self.running_until = Some(Stop::Time(since_epoch.num_milliseconds() as f64 / 1000.0));
+            }
+            Control::RunUntil(RunUntil::FirstDepletion) => {
+                self.running_until = Some(Stop::Depleted(self.engine.depleted_count()));
+            }
+            Control::RunUntil(RunUntil::Relays(count)) => {
+                self.running_until = Some(Stop::Relays(self.engine.relay_count() + count));
+            }
+            Control::Snapshot => props.on_snapshot.emit(self.engine.snapshot()),
+            Control::Restore(snapshot) => {
+                // The panel only sends snapshots that parsed, which validates them
+                if let Ok(engine) = Engine::from_snapshot(Snapshot::clone(&snapshot)) {
+                    self.selected_satellite_id = None;
+                    self.recorder.clear();
+                    self.engine = engine;
                 }
+            }
+        }
+    }
+
+    fn set_selected(&mut self, id: usize, selected: bool) {
+        self.engine.set_selected(id, selected);
+        if let Some(replay) = &mut self.replay {
+            replay.props[id].set_selected(selected);
+        }
+    }
+
+    /// Slider over the recorded frames, with the replay controls
+    fn view_timeline(&self, ctx: &Context<Self>) -> Html {
+        if self.recorder.is_empty() {
+            return html! {};
+        }
 
-            </svg>
+        let link = ctx.link();
+        let last = self.recorder.len() - 1;
+        let current = self.replay.as_ref().map_or(last, |replay| replay.frame);
+        let playing = self.replay.as_ref().is_some_and(|replay| replay.playing);
+        let round = self.recorder.get(current).map_or(0, |frame| frame.game_round);
+        let oninput = link.callback(|e: InputEvent| {
+            let input: HtmlInputElement = e.target_unchecked_into();
+            Msg::Scrub(input.value().parse().unwrap_or_default())
+        });
+
+        html! {
+            <div class="timeline">
+                <button onclick={link.callback(|_| Msg::ToggleReplay)}>
+                    { if playing { "Pause Replay" } else { "Replay" } }
+                </button>
+                <input type="range" class="timeline__slider" min="0" max={last.to_string()}
+                    value={current.to_string()} {oninput} />
+                <span>{ format!("Round {round}") }</span>
+                <button onclick={link.callback(|_| Msg::GoLive)} disabled={self.replay.is_none()}>{ "Live" }</button>
+            </div>
         }
     }
 }
+
+/// Interval sending a tick every `tick_interval_ms` of real time
+fn tick_interval(ctx: &Context<Simulation>) -> Interval {
+    let link = ctx.link().clone();
+    Interval::new(ctx.props().scenario.settings.tick_interval_ms as u32, move || {
+        link.send_message(Msg::Tick)
+    })
+}
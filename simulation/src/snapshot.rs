@@ -0,0 +1,161 @@
+//! This module keeps the full state of a running simulation: the scenario, the
+//! satellites, the clusters, the schedule, the clock and the state of the random
+//! number generator, so a run can be saved at an interesting moment and later
+//! restored to continue exactly as it would have.
+
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::clock::SimClock;
+use crate::cluster::ClusterMap;
+use crate::engine::Activity;
+use crate::events::EventRecord;
+use crate::satellite::{SatelliteEnergy, SatellitePosition, SatelliteProperties};
+use crate::scenario::Scenario;
+use crate::scheduler::Scheduler;
+
+/// Version of the snapshots written by this build. Bump it when the state of
+/// the simulation changes, earlier snapshots can't be restored.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub scenario: Scenario,
+    pub(crate) game_round: u64,
+    pub(crate) clock: SimClock,
+    pub(crate) scheduler: Scheduler<Activity>,
+    pub(crate) props: Vec<SatelliteProperties>,
+    pub(crate) positions: Vec<SatellitePosition>,
+    pub(crate) energy: Vec<SatelliteEnergy>,
+    pub(crate) cluster_map: ClusterMap,
+    /// Events since the last report
+    pub(crate) events: Vec<EventRecord>,
+    pub(crate) rng: ChaCha8Rng,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SnapshotError {
+    /// The file is not a valid snapshot
+    Parse(String),
+    /// The snapshot was written by another version of the simulation
+    UnsupportedVersion(u32),
+    /// The parts of the snapshot don't describe the same satellites, e.g. after editing it by hand
+    Inconsistent(String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Parse(e) => write!(f, "invalid snapshot: {e}"),
+            SnapshotError::UnsupportedVersion(v) => write!(
+                f,
+                "snapshot version {v} is not supported, this build restores version {SNAPSHOT_VERSION}"
+            ),
+            SnapshotError::Inconsistent(e) => write!(f, "inconsistent snapshot: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl Snapshot {
+    pub fn game_round(&self) -> u64 {
+        self.game_round
+    }
+
+    /// Simulated UTC time the snapshot was taken at
+    pub fn time(&self) -> DateTime<Utc> {
+        self.clock.now()
+    }
+
+    pub fn parse(text: &str) -> Result<Self, SnapshotError> {
+        #[derive(Deserialize)]
+        struct Versioned {
+            version: u32,
+        }
+
+        // Check the version first so snapshots of other versions get a clear error
+        let Versioned { version } = serde_json::from_str(text).map_err(|e| SnapshotError::Parse(e.to_string()))?;
+        if version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(version));
+        }
+        let snapshot: Snapshot = serde_json::from_str(text).map_err(|e| SnapshotError::Parse(e.to_string()))?;
+        snapshot.validate()?;
+        Ok(snapshot)
+    }
+
+    /// Check that every satellite has its properties, position and energy, and that the
+    /// clusters only refer to those satellites
+    pub fn validate(&self) -> Result<(), SnapshotError> {
+        let count = self.props.len();
+        if self.positions.len() != count || self.energy.len() != count {
+            return Err(SnapshotError::Inconsistent(format!(
+                "{count} satellites have {} positions and {} energy states",
+                self.positions.len(),
+                self.energy.len()
+            )));
+        }
+
+        let ids = self.props.iter().map(|p| p.id()).zip(self.energy.iter().map(|e| e.id()));
+        if let Some(index) = ids.enumerate().position(|(index, (props, energy))| props != index || energy != index) {
+            return Err(SnapshotError::Inconsistent(format!("satellite {index} is stored under another id")));
+        }
+
+        for cluster in self.cluster_map.clusters() {
+            if let Some(&id) = cluster.members().iter().chain([&cluster.head()]).find(|&&id| id >= count) {
+                return Err(SnapshotError::Inconsistent(format!(
+                    "cluster {} refers to satellite {id}, there are {count} satellites",
+                    cluster.head()
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> String {
+        // Serializing plain structs never fails.
+        serde_json::to_string(self).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cluster::Cluster;
+    use crate::engine::{Activity, Engine};
+    use crate::settings::Settings;
+
+    fn snapshot() -> Snapshot {
+        let mut engine = Engine::new(Scenario::new(Settings { boids: 10, ..Default::default() }, 49));
+        engine.step_until(Activity::Game, |_| ());
+        engine.snapshot()
+    }
+
+    #[test]
+    fn rejects_missing_satellite_state() {
+        let mut edited = snapshot();
+        edited.energy.pop();
+
+        assert!(matches!(Snapshot::parse(&edited.to_json()), Err(SnapshotError::Inconsistent(_))));
+        assert!(Engine::from_snapshot(edited).is_err());
+    }
+
+    #[test]
+    fn rejects_clusters_of_unknown_satellites() {
+        let mut edited = snapshot();
+        let mut cluster = Cluster::new(3);
+        cluster.add_member(10);
+        edited.cluster_map.insert(cluster);
+
+        assert!(matches!(Snapshot::parse(&edited.to_json()), Err(SnapshotError::Inconsistent(_))));
+
+        let mut edited = snapshot();
+        edited.cluster_map.insert(Cluster::new(12));
+        assert!(Engine::from_snapshot(edited).is_err());
+    }
+}
@@ -1,8 +1,42 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+use std::path::{Path, PathBuf};
+
+/// Write an export from the simulation to the downloads folder, returning the written path
+#[tauri::command]
+fn save_export(file_name: String, contents: String) -> Result<String, String> {
+  let dir = tauri::api::path::download_dir().ok_or("Couldn't find the downloads folder")?;
+  let file_name = Path::new(&file_name).file_name().ok_or("Invalid export file name")?;
+  let path = dir.join(file_name);
+
+  std::fs::write(&path, contents).map_err(|e| e.to_string())?;
+  Ok(path.display().to_string())
+}
+
+/// Path of a file in the configuration folder of the application, creating the folder if needed
+fn config_file(app: &tauri::AppHandle, file_name: &str) -> Result<PathBuf, String> {
+  let dir = app.path_resolver().app_config_dir().ok_or("Couldn't find the configuration folder")?;
+  std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+  let file_name = Path::new(file_name).file_name().ok_or("Invalid file name")?;
+  Ok(dir.join(file_name))
+}
+
+/// Read the settings presets saved by the simulation
+#[tauri::command]
+fn load_presets(app: tauri::AppHandle, file_name: String) -> Result<String, String> {
+  std::fs::read_to_string(config_file(&app, &file_name)?).map_err(|e| e.to_string())
+}
+
+/// Write the settings presets of the simulation
+#[tauri::command]
+fn save_presets(app: tauri::AppHandle, file_name: String, contents: String) -> Result<(), String> {
+  std::fs::write(config_file(&app, &file_name)?, contents).map_err(|e| e.to_string())
+}
+
 fn main() {
   tauri::Builder::default()
+    .invoke_handler(tauri::generate_handler![save_export, load_presets, save_presets])
     .run(tauri::generate_context!())
     .expect("error while running tauri application");
 }